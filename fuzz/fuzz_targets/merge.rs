@@ -0,0 +1,61 @@
+// タイルを動かしてマージする処理を、任意の盤面と操作の列で試す
+//
+// 入力: [幅, 高さ, マスのランク..., 操作の向き...]
+//   cargo fuzz run merge
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rust_2048_clone::{bitboard::Bitboard, game::{check_board_size, Direction, GameState}, rng::GameRng};
+
+fuzz_target!(|data: &[u8]| {
+    if data.len() < 2 {
+        return;
+    }
+    let (width, height) = (1 + (data[0] % 8) as i32, 1 + (data[1] % 8) as i32);
+    if check_board_size(width, height).is_err() {
+        return;
+    }
+
+    let cells = (width * height) as usize;
+    let rest = &data[2..];
+    if rest.len() < cells {
+        return;
+    }
+    let grid: Vec<i32> = rest[..cells].iter()
+        .map(|&b| match b % 12 {
+            0 => 0,
+            rank => 1 << rank,
+        })
+        .collect();
+    let mut game = GameState::from_parts(width, height, grid, 0, 0, GameRng::new(0)).unwrap();
+
+    for &b in rest[cells..].iter() {
+        let direction = Direction::ALL[(b % 4) as usize];
+        let before = game.clone();
+        let outcome = game.slide(direction);
+
+        // タイルの合計は変わらない
+        let sum = |g: &GameState| g.grid().iter().map(|&s| s as i64).sum::<i64>();
+        assert_eq!(sum(&game), sum(&before));
+
+        // スコアはマージした分だけ増える
+        let merged: i32 = outcome.moves.iter()
+            .filter(|m| m.merged)
+            .map(|m| before.get(m.from_x, m.from_y) * 2)
+            .sum();
+        assert_eq!(outcome.score_gained, merged);
+        assert_eq!(game.score(), before.score() + merged);
+        assert_eq!(outcome.moved, game.grid() != before.grid());
+
+        // 4x4 の場合は Bitboard と同じ結果になる。Bitboard で表せないのは 32768 より大きいタイルができる場合だけ
+        if let Some(board) = Bitboard::from_game(&before) {
+            match board.slide(direction) {
+                Some((slid, score)) => {
+                    assert_eq!(slid.to_grid(), game.grid());
+                    assert_eq!(score, outcome.score_gained);
+                },
+                None => assert!(game.max_tile() > 32768),
+            }
+        }
+    }
+});
@@ -0,0 +1,283 @@
+use crate::{bitboard::{Bitboard, MAX_RANK}, game::{Direction, GameState}};
+
+// 生成されるタイルの確率。GameState::generate_tile と合わせる
+static PROBABILITY_2: f64 = 0.9;
+static PROBABILITY_4: f64 = 0.1;
+
+// 評価関数の重み
+static EMPTY_WEIGHT: f64 = 2.7;
+static MONOTONICITY_WEIGHT: f64 = 1.0;
+static SMOOTHNESS_WEIGHT: f64 = 0.1;
+static MAX_TILE_WEIGHT: f64 = 1.0;
+
+// タイル生成の手番で調べる空きマスの最大数。多い場合は間引いて、大きい盤面でも探索の時間を抑える
+// 4x4 では序盤しか間引かない
+static MAX_SPAWN_CELLS: usize = 8;
+
+// 動かせない盤面の評価値
+static DEAD_END_SCORE: f64 = -1.0e6;
+
+// 探索に使う盤面。4x4 の場合は速い Bitboard を、それ以外は GameState を使う
+// Bitboard は 32768 より大きいタイルを表せないため、探索中にできる可能性がある場合も GameState を使う
+trait SearchBoard: Sized {
+    fn width(&self) -> i32;
+    fn height(&self) -> i32;
+    // タイルのスコアの2の何乗か。空きマスは0
+    fn rank(&self, x: i32, y: i32) -> f64;
+    // 動かした盤面。動かない場合は None
+    fn slide(&self, direction: Direction) -> Option<Self>;
+    fn empty_cells(&self) -> Vec<(i32, i32)>;
+    fn with_tile(&self, x: i32, y: i32, score: i32) -> Self;
+}
+
+impl SearchBoard for GameState {
+    fn width(&self) -> i32 {
+        GameState::width(self)
+    }
+
+    fn height(&self) -> i32 {
+        GameState::height(self)
+    }
+
+    fn rank(&self, x: i32, y: i32) -> f64 {
+        let score = self.get(x, y);
+        if score == 0 { 0.0 } else { (score as f64).log2() }
+    }
+
+    fn slide(&self, direction: Direction) -> Option<GameState> {
+        let mut next = self.clone();
+        if GameState::slide(&mut next, direction).moved { Some(next) } else { None }
+    }
+
+    fn empty_cells(&self) -> Vec<(i32, i32)> {
+        GameState::empty_cells(self)
+    }
+
+    fn with_tile(&self, x: i32, y: i32, score: i32) -> GameState {
+        let mut next = self.clone();
+        next.place_tile(x, y, score);
+        next
+    }
+}
+
+impl SearchBoard for Bitboard {
+    fn width(&self) -> i32 {
+        4
+    }
+
+    fn height(&self) -> i32 {
+        4
+    }
+
+    fn rank(&self, x: i32, y: i32) -> f64 {
+        Bitboard::rank(*self, x, y) as f64
+    }
+
+    // 32768 同士のマージは best_direction で起きない盤面だけ探索する
+    fn slide(&self, direction: Direction) -> Option<Bitboard> {
+        let (next, _) = Bitboard::slide(*self, direction)?;
+        if next != *self { Some(next) } else { None }
+    }
+
+    fn empty_cells(&self) -> Vec<(i32, i32)> {
+        Bitboard::empty_cells(*self)
+    }
+
+    fn with_tile(&self, x: i32, y: i32, score: i32) -> Bitboard {
+        Bitboard::with_tile(*self, x, y, score)
+    }
+}
+
+// 生成されるタイルの期待値を取る expectimax で、depth 手先まで探索して一番良い向きを返す
+// 動かせる向きがない場合は None
+pub fn best_direction(game: &GameState, depth: u32) -> Option<Direction> {
+    // 1手で最大のタイルのランクは1つまでしか上がらないため、depth 手の間に MAX_RANK を超えない場合だけ使える
+    match Bitboard::from_game(game).filter(|board| board.max_rank() as u32 + depth <= MAX_RANK as u32) {
+        Some(board) => search(&board, depth),
+        None => search(game, depth),
+    }
+}
+
+fn search<B: SearchBoard>(board: &B, depth: u32) -> Option<Direction> {
+    let mut best: Option<(Direction, f64)> = None;
+
+    for &direction in Direction::ALL.iter() {
+        let next = match board.slide(direction) {
+            Some(next) => next,
+            None => continue,
+        };
+
+        let value = chance_node(&next, depth.saturating_sub(1));
+        if best.is_none_or(|(_, best_value)| value > best_value) {
+            best = Some((direction, value));
+        }
+    }
+
+    best.map(|(direction, _)| direction)
+}
+
+// プレイヤーの手番: 一番良い向きの評価値
+fn max_node<B: SearchBoard>(board: &B, depth: u32) -> f64 {
+    if depth == 0 {
+        return evaluate_board(board);
+    }
+
+    let mut best = None;
+    for &direction in Direction::ALL.iter() {
+        let next = match board.slide(direction) {
+            Some(next) => next,
+            None => continue,
+        };
+        let value = chance_node(&next, depth - 1);
+        if best.is_none_or(|best_value| value > best_value) {
+            best = Some(value);
+        }
+    }
+
+    best.unwrap_or(DEAD_END_SCORE)
+}
+
+// タイル生成の手番: 空きマスと生成されるスコアごとの評価値の期待値
+// 空きマスが MAX_SPAWN_CELLS より多い場合は、等間隔に選んだマスだけで期待値を取る
+fn chance_node<B: SearchBoard>(board: &B, depth: u32) -> f64 {
+    let empty = board.empty_cells();
+    if empty.is_empty() {
+        return max_node(board, depth);
+    }
+
+    let step = empty.len().div_ceil(MAX_SPAWN_CELLS);
+    let mut total = 0.0;
+    let mut count = 0;
+    for &(x, y) in empty.iter().step_by(step) {
+        for &(score, probability) in [(2, PROBABILITY_2), (4, PROBABILITY_4)].iter() {
+            total += probability * max_node(&board.with_tile(x, y, score), depth);
+        }
+        count += 1;
+    }
+
+    total / count as f64
+}
+
+// 盤面の評価値: 空きマスの数、単調さ、隣り合うタイルの近さ、最大のタイル
+pub fn evaluate(game: &GameState) -> f64 {
+    evaluate_board(game)
+}
+
+fn evaluate_board<B: SearchBoard>(board: &B) -> f64 {
+    let (width, height) = (board.width(), board.height());
+    let rank = |x: i32, y: i32| board.rank(x, y);
+
+    let empty = board.empty_cells().len() as f64;
+
+    // 隣り合うタイルのランクの差が小さいほど良い
+    let mut smoothness = 0.0;
+    let mut max_rank: f64 = 0.0;
+    for y in 0..height {
+        for x in 0..width {
+            if rank(x, y) == 0.0 {
+                continue;
+            }
+            max_rank = max_rank.max(rank(x, y));
+            if x + 1 < width && rank(x + 1, y) != 0.0 {
+                smoothness -= (rank(x, y) - rank(x + 1, y)).abs();
+            }
+            if y + 1 < height && rank(x, y + 1) != 0.0 {
+                smoothness -= (rank(x, y) - rank(x, y + 1)).abs();
+            }
+        }
+    }
+
+    // 各行・各列で、ランクが一方向に並んでいるほど良い
+    let line_monotonicity = |ranks: Vec<f64>| -> f64 {
+        let mut increasing = 0.0;
+        let mut decreasing = 0.0;
+        for pair in ranks.windows(2) {
+            if pair[0] > pair[1] {
+                decreasing += pair[1] - pair[0];
+            } else {
+                increasing += pair[0] - pair[1];
+            }
+        }
+        f64::max(increasing, decreasing)
+    };
+    let mut monotonicity = 0.0;
+    for y in 0..height {
+        monotonicity += line_monotonicity((0..width).map(|x| rank(x, y)).collect());
+    }
+    for x in 0..width {
+        monotonicity += line_monotonicity((0..height).map(|y| rank(x, y)).collect());
+    }
+
+    EMPTY_WEIGHT * empty
+        + MONOTONICITY_WEIGHT * monotonicity
+        + SMOOTHNESS_WEIGHT * smoothness
+        + MAX_TILE_WEIGHT * max_rank
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_the_only_legal_move() {
+        // 左だけ盤面が変わる
+        let game = GameState::from_rows(&[
+            &[0, 2, 4, 8],
+            &[0, 4, 8, 16],
+            &[0, 2, 4, 8],
+            &[0, 4, 8, 16],
+        ]);
+        assert_eq!(best_direction(&game, 2), Some(Direction::Left));
+
+        // 4x4 以外は GameState で探索する
+        let game = GameState::from_rows(&[
+            &[0, 2, 4],
+            &[0, 4, 8],
+            &[0, 2, 4],
+        ]);
+        assert_eq!(best_direction(&game, 2), Some(Direction::Left));
+    }
+
+    #[test]
+    fn searches_the_largest_board() {
+        // 空きマスを間引くため、32x32 の盤面でもすぐに終わる
+        let mut game = GameState::from_rows(&[&[0; 32][..]; 32]);
+        game.place_tile(0, 0, 2);
+        game.place_tile(5, 0, 2);
+        game.place_tile(3, 7, 4);
+        let direction = best_direction(&game, 2);
+        assert!(direction.is_some_and(|d| GameState::slide(&mut game.clone(), d).moved), "{:?}", direction);
+    }
+
+    #[test]
+    fn returns_none_when_nothing_moves() {
+        let game = GameState::from_rows(&[
+            &[2, 4],
+            &[4, 2],
+        ]);
+        assert_eq!(best_direction(&game, 2), None);
+    }
+
+    #[test]
+    fn prefers_the_merge() {
+        // 下は動くだけで、左右は 512 同士がマージする
+        let game = GameState::from_rows(&[
+            &[512, 512, 0, 0],
+            &[0, 0, 0, 0],
+            &[0, 0, 0, 0],
+            &[0, 0, 0, 0],
+        ]);
+        let direction = best_direction(&game, 2);
+        assert!(direction == Some(Direction::Left) || direction == Some(Direction::Right), "{:?}", direction);
+
+        // Bitboard で表せない 65536 を作るマージも GameState で探索する
+        let game = GameState::from_rows(&[
+            &[32768, 32768, 0, 0],
+            &[0, 0, 0, 0],
+            &[0, 0, 0, 0],
+            &[0, 0, 0, 0],
+        ]);
+        let direction = best_direction(&game, 2);
+        assert!(direction == Some(Direction::Left) || direction == Some(Direction::Right), "{:?}", direction);
+    }
+}
@@ -1,13 +1,50 @@
+use std::collections::VecDeque;
 use std::path::{PathBuf, Path};
+use std::rc::Rc;
 
 use opengl_graphics::{GlGraphics, Texture as GlTexture};
 use piston_window::*;
-use crate::{board::Board, settings::Settings, number_renderer::NumberRenderer};
+use crate::{ai, best_score::BestScore, board::Board, game::Direction, gamepad::{Controllers, Gamepad}, key_bindings::Action, playback::Playback, replay::Replay, rng::GameRng, saved_game::SavedGame, swipe::Swipe,
+    strategy::{strategy_by_name, Strategy, STRATEGY_NAMES}, settings::{Settings, SettingsError}, settings_watcher::SettingsWatcher, number_renderer::NumberRenderer, text_renderer::TextRenderer};
 
-pub struct App<'a> {
-    board: Board<'a>,
+// 途中のゲームを自動保存する間隔 (秒)
+static AUTOSAVE_INTERVAL: f64 = 10.0;
+// ヒントを探す時の探索の深さ
+static HINT_DEPTH: u32 = 2;
+
+pub struct App {
+    board: Board,
+    best_score: BestScore,
+    // コマンドラインで指定したシード。指定した場合、新しいゲームは全てこのシードで始める
+    seed: Option<u64>,
+    // 起動時に再開するか確認中の保存データ
+    resume_offer: Option<SavedGame>,
+    // 前回の自動保存からの経過時間
+    autosave_elapsed: f64,
+    // このゲームのリプレイを保存したか
+    replay_saved: bool,
+    // リプレイの再生中
+    playback: Option<Playback>,
+    // AIがおすすめする向き。次の操作で消える
+    hint: Option<Direction>,
+    // アニメーション中に入力された向き。タイルが止まったら順に動かす
+    pending_directions: VecDeque<Direction>,
+    // 終了の操作をしたか
+    quit_requested: bool,
+    swipe: Swipe,
+    controllers: Controllers,
+    gamepad: Gamepad,
+    // 自動プレイに使える戦略と、選んでいる戦略
+    strategies: Vec<Box<dyn Strategy>>,
+    strategy_index: usize,
+    autoplay: bool,
+    // 今のゲームで自動プレイが動かしたか。自動プレイのスコアはベストスコアに残さない
+    bot_played: bool,
     number_renderer: Option<NumberRenderer>,
-    settings: &'a Settings,
+    text_renderer: TextRenderer,
+    // 設定ファイルが変わったら読み込み直して差し替える
+    settings: Rc<Settings>,
+    settings_watcher: SettingsWatcher,
 
     logo: Option<GlTexture>,
     comment1: Option<GlTexture>,
@@ -17,12 +54,45 @@ pub struct App<'a> {
 
 fn rgb2rgba(c: [f32; 3]) -> [f32; 4] { [c[0], c[1], c[2], 1.0] }
 
-impl<'a> App<'a> {
-    pub fn new(settings: &'a Settings) -> App {
+impl App {
+    pub fn new(settings: Rc<Settings>, seed: Option<u64>, saved_game: Option<SavedGame>) -> App {
+        // 今の設定の盤面で復元できる保存データだけ再開を提案する
+        // シードを指定した場合は、そのシードのゲームを始める
+        let resume_offer = saved_game
+            .filter(|saved| {
+                let fits = saved.fits(&settings);
+                if !fits {
+                    println!("WARNING: The saved game doesn't fit the {}x{} board in the settings. Starting a new game.",
+                        settings.tile_width, settings.tile_height);
+                }
+                fits
+            })
+            .filter(|_| seed.is_none());
+
         App {
-            board: Board::new(settings),
-            number_renderer: Some(NumberRenderer::new()),
+            board: Board::new(settings.clone(), App::new_rng(seed)),
+            best_score: BestScore::load(),
+            seed: seed,
+            resume_offer: resume_offer,
+            autosave_elapsed: 0.0,
+            replay_saved: false,
+            playback: None,
+            hint: None,
+            pending_directions: VecDeque::new(),
+            quit_requested: false,
+            swipe: Swipe::new(settings.swipe_min_distance),
+            controllers: Controllers::new(),
+            gamepad: Gamepad::new(&settings),
+            strategies: STRATEGY_NAMES.iter()
+                .filter_map(|name| strategy_by_name(name, rand::random()))
+                .collect(),
+            strategy_index: STRATEGY_NAMES.len() - 1,
+            autoplay: false,
+            bot_played: false,
+            number_renderer: Some(NumberRenderer::new(Path::new(&settings.asset_folder))),
+            text_renderer: TextRenderer::new(),
             settings: settings,
+            settings_watcher: SettingsWatcher::new(),
 
             logo: None,
             comment1: None,
@@ -31,6 +101,22 @@ impl<'a> App<'a> {
         }
     }
     
+    // リプレイの再生を始める。再生中はプレイの操作と保存をしない
+    pub fn start_playback(&mut self, replay: Replay) {
+        let playback = Playback::new(replay);
+        self.board = Board::from_game(self.settings.clone(), playback.initial_state(), Vec::new(), true, 0);
+        self.resume_offer = None;
+        self.replay_saved = true;
+        self.playback = Some(playback);
+    }
+
+    fn new_rng(seed: Option<u64>) -> GameRng {
+        match seed {
+            Some(seed) => GameRng::new(seed),
+            None => GameRng::from_entropy(),
+        }
+    }
+
     pub fn load(&mut self) {
         // アセットフォルダを読み込む
         let mut asset_root = PathBuf::new();
@@ -45,12 +131,21 @@ impl<'a> App<'a> {
         let mut comment2_path = asset_root.clone();
         comment2_path.push(Path::new("comment2.png"));
 
-        let texture_settings = TextureSettings::new();
-        // 画像を読み込む
-        self.logo = Some(GlTexture::from_path(&logo_path, &texture_settings).unwrap());
+        // 画像を読み込む。読み込めない画像は描画しない
+        self.logo = App::load_texture(&logo_path);
         // コメントを読み込む
-        self.comment1 = Some(GlTexture::from_path(&comment1_path, &texture_settings).unwrap());
-        self.comment2 = Some(GlTexture::from_path(&comment2_path, &texture_settings).unwrap());
+        self.comment1 = App::load_texture(&comment1_path);
+        self.comment2 = App::load_texture(&comment2_path);
+    }
+
+    fn load_texture(path: &Path) -> Option<GlTexture> {
+        match GlTexture::from_path(path, &TextureSettings::new()) {
+            Ok(texture) => Some(texture),
+            Err(e) => {
+                println!("WARNING: Failed to load {}: {}", path.display(), e);
+                None
+            },
+        }
     }
 
     pub fn render(&mut self, args: &RenderArgs, gl: &mut GlGraphics) {
@@ -68,31 +163,156 @@ impl<'a> App<'a> {
             clear(w_bg_col, gl);
             self.render_ui(c, gl);
             self.board.render(nr.iter().next().unwrap(), c, gl);
+            if let Some(direction) = self.hint {
+                self.render_hint(direction, c, gl);
+            }
+            // 再開の確認中、勝った場合、ゲームオーバーの場合、ボードの上に表示
+            if let Some(ref saved) = self.resume_offer {
+                self.render_resume_offer(saved, c, gl);
+            } else if self.board.is_won() {
+                self.render_win(c, gl);
+            } else if self.board.is_game_over() {
+                self.render_game_over(c, gl);
+            }
         })
     }
     
     fn render_ui(&self, c: &Context, gl: &mut GlGraphics) {
         // ロゴを描画
-        Image::new_color(rgb2rgba(self.settings.text_dark_color))
-            .draw(self.logo.iter().next().unwrap(),
+        if let Some(ref logo) = self.logo {
+            Image::new_color(rgb2rgba(self.settings.text_dark_color))
+                .draw(logo,
+                    &DrawState::default(),
+                    c.trans(self.settings.board_padding,self.settings.board_padding).transform,
+                    gl);
+        }
+
+        // スコアボードを描画
+        let score = self.board.game().score();
+        self.render_score_box("SCORE", score, self.settings.score_rect, c, gl);
+        let best = self.best_score.score();
+        self.render_score_box("BEST", best, self.settings.best_rect, c, gl);
+
+        // シードを描画。同じシードで同じゲームを再現できる
+        // リプレイの再生中は再生状態を描画
+        // 自動プレイ中は戦略の名前を描画
+        let seed = match self.playback {
+            Some(ref playback) => playback.status_text(),
+            None if self.autoplay => format!("AUTO: {}", self.strategies[self.strategy_index].name()),
+            None => format!("SEED {}", self.board.game().rng().seed()),
+        };
+        let seed_x = self.settings.board_padding;
+        let seed_w = self.settings.score_rect[0] - 2.0 * seed_x;
+        let seed_y = self.settings.comment1_offset_y - self.settings.board_padding;
+        self.text_renderer.render(&seed, seed_x + seed_w / 2.0, seed_y, seed_w,
+            self.settings.text_dark_color, c, gl);
+
+        // コメントを描画
+        if let Some(ref comment1) = self.comment1 {
+            App::render_comment(&self.settings, comment1, self.settings.comment1_offset_y, c, gl);
+        }
+        if let Some(ref comment2) = self.comment2 {
+            App::render_comment(&self.settings, comment2, self.settings.comment2_offset_y, c, gl);
+        }
+    }
+
+    // おすすめの向きのボードの端を強調する
+    fn render_hint(&self, direction: Direction, c: &Context, gl: &mut GlGraphics) {
+        let x = self.settings.board_padding;
+        let y = self.settings.board_padding + self.settings.board_offset_y;
+        let (w, h) = (self.settings.board_size[0], self.settings.board_size[1]);
+        let t = self.settings.tile_padding / 2.0;
+
+        let rect = match direction {
+            Direction::Up => [x, y, w, t],
+            Direction::Down => [x, y + h - t, w, t],
+            Direction::Left => [x, y, t, h],
+            Direction::Right => [x + w - t, y, t, h],
+        };
+        Rectangle::new(rgb2rgba(self.settings.button_color))
+            .draw(rect,
                 &DrawState::default(),
-                c.trans(self.settings.board_padding,self.settings.board_padding).transform,
+                c.transform,
                 gl);
+    }
 
-        // スコアボードを描画
+    fn render_score_box(&self, label: &str, score: i32, rect: [f64; 4], c: &Context, gl: &mut GlGraphics) {
         Rectangle::new(rgb2rgba(self.settings.label_color))
-            .draw(self.settings.best_rect,
+            .draw(rect,
                 &DrawState::default(),
                 c.transform,
                 gl);
 
-        // コメントを描画
-        let comment1_offset_y = self.settings.comment1_offset_y;
-        let comment1 = self.comment1.as_ref().unwrap();
-        App::render_comment(self.settings, comment1, comment1_offset_y, c, gl);
-        let comment2_offset_y = self.settings.comment2_offset_y;
-        let comment2 = self.comment2.as_ref().unwrap();
-        App::render_comment(self.settings, comment2, comment2_offset_y, c, gl);
+        // 上部にラベル、下部にスコアを描画
+        let center_x = rect[0] + rect[2] / 2.0;
+        self.text_renderer.render(label, center_x, rect[1] + rect[3] * 0.2, rect[2] * 0.4,
+            self.settings.text_light_color, c, gl);
+        self.number_renderer.as_ref().unwrap().render(score as u32, center_x, rect[1] + rect[3] * 0.65,
+            rect[2], self.settings.text_light_color, c, gl);
+    }
+
+    fn render_resume_offer(&self, saved: &SavedGame, c: &Context, gl: &mut GlGraphics) {
+        let lines = [
+            "RESUME SAVED GAME?".to_string(),
+            format!("SCORE: {}", saved.score()),
+            self.press_to(Action::KeepGoing, "RESUME"),
+            self.press_to(Action::Restart, "START A NEW GAME"),
+        ];
+        self.render_overlay(&lines, c, gl);
+    }
+
+    fn render_win(&self, c: &Context, gl: &mut GlGraphics) {
+        let game = self.board.game();
+        let lines = [
+            "YOU WIN!".to_string(),
+            format!("SCORE: {}", game.score()),
+            self.press_to(Action::KeepGoing, "KEEP GOING"),
+            self.press_to(Action::Restart, "RESTART"),
+        ];
+        self.render_overlay(&lines, c, gl);
+    }
+
+    fn render_game_over(&self, c: &Context, gl: &mut GlGraphics) {
+        let game = self.board.game();
+        let lines = [
+            "GAME OVER!".to_string(),
+            format!("SCORE: {}", game.score()),
+            format!("MAX TILE: {}", game.max_tile()),
+            self.press_to(Action::Restart, "RESTART"),
+        ];
+        self.render_overlay(&lines, c, gl);
+    }
+
+    // 操作に割り当てたキーの案内 ("PRESS SPACE TO RESTART" など)
+    fn press_to(&self, action: Action, what: &str) -> String {
+        format!("PRESS {} TO {}", self.settings.key_bindings.key_name(action), what)
+    }
+
+    fn render_overlay(&self, lines: &[String], c: &Context, gl: &mut GlGraphics) {
+        let x = self.settings.board_padding;
+        let y = self.settings.board_padding + self.settings.board_offset_y;
+        let (w, h) = (self.settings.board_size[0], self.settings.board_size[1]);
+        let bg = self.settings.window_background_color;
+
+        // ボードを半透明の背景色で覆う
+        Rectangle::new([bg[0], bg[1], bg[2], 0.73])
+            .draw([x, y, w, h],
+                &DrawState::default(),
+                c.transform,
+                gl);
+
+        // 文字列をボードの中央に縦に並べる
+        let line_height = 40.0;
+        let top = y + h / 2.0 - line_height * (lines.len() - 1) as f64 / 2.0;
+        for (i, line) in lines.iter().enumerate() {
+            self.text_renderer.render(
+                line,
+                x + w / 2.0,
+                top + line_height * i as f64,
+                w - 2.0 * self.settings.tile_padding,
+                self.settings.text_dark_color,
+                c, gl);
+        }
     }
 
     fn render_comment(settings: &Settings, comment: &GlTexture, y: f64, c: &Context, gl: &mut GlGraphics) {
@@ -109,33 +329,310 @@ impl<'a> App<'a> {
     }
 
     pub fn update(&mut self, args: &UpdateArgs) {
+        if let Some(result) = self.settings_watcher.poll(args.dt) {
+            self.reload_settings(result);
+        }
+        self.swipe.update(args.dt);
+
+        // コントローラーの操作。再開の確認中はキーボードで答える
+        for event in self.controllers.poll() {
+            self.gamepad.handle(event);
+        }
+        for action in self.gamepad.update(args.dt) {
+            if self.resume_offer.is_none() {
+                self.handle_action(action);
+            }
+        }
+
+        // リプレイの再生中は再生速度に合わせてアニメーションも速くする
+        if let Some(ref mut playback) = self.playback {
+            self.board.update(args.dt * playback.speed());
+            if !self.board.is_locking() {
+                if let Some(direction) = playback.tick(args.dt) {
+                    self.board.apply_move(direction);
+                }
+            }
+            return;
+        }
+
         self.board.update(args.dt);
+
+        // タイルが止まったら、順番待ちの向きに動かす。勝って動かせない場合は捨てる
+        if !self.board.is_locking() {
+            if let Some(direction) = self.pending_directions.pop_front() {
+                if self.board.apply_move(direction).is_none() {
+                    self.pending_directions.clear();
+                }
+            }
+        }
+
+        // 自動プレイ中は、タイルが止まるたびに戦略が選んだ向きに動かす
+        if self.autoplay && !self.board.is_locking() && !self.board.game().is_game_over() {
+            // 勝った後もそのまま続ける
+            if self.board.is_won() {
+                self.board.keep_going();
+            }
+            let direction = self.strategies[self.strategy_index].choose(self.board.game());
+            self.board.apply_move(direction);
+            // 盤面が変わるので、前のヒントは消す
+            self.hint = None;
+            self.bot_played = true;
+        }
+
+        // スコアが増えた時にベストスコアも更新。自動プレイしたゲームは除く
+        if !self.bot_played {
+            self.best_score.update(self.board.game().score());
+        }
+
+        // ゲームオーバーになったらリプレイを保存
+        if self.board.is_game_over() && !self.replay_saved {
+            self.replay_saved = true;
+            self.save_replay();
+        }
+
+        // 一定時間ごとに途中のゲームを保存
+        self.autosave_elapsed += args.dt;
+        if self.autosave_elapsed >= AUTOSAVE_INTERVAL {
+            self.autosave_elapsed = 0.0;
+            self.save_game();
+        }
+    }
+
+    // 読み込み直した設定に差し替える。色、アニメーションの時間、配置は今のゲームのまま変わる
+    // 読めない設定や、盤面の大きさが変わる設定は使わず、今の設定のまま続ける
+    fn reload_settings(&mut self, result: Result<Settings, SettingsError>) {
+        let settings = match result {
+            Ok(settings) => settings,
+            Err(e) => {
+                println!("WARNING: {}", e);
+                println!("WARNING: Keeping the current settings.");
+                return;
+            },
+        };
+        // 盤面の大きさや目標のタイルが変わると、リプレイのルールと合わなくなる
+        if let Err(e) = self.settings.check_reload(&settings) {
+            println!("WARNING: {}. Keeping the current settings.", e);
+            return;
+        }
+
+        let settings = Rc::new(settings);
+        self.board.set_settings(settings.clone());
+        // スワイプとコントローラーは設定の値を持っているため作り直す
+        self.swipe = Swipe::new(settings.swipe_min_distance);
+        self.gamepad = Gamepad::new(&settings);
+        self.settings = settings;
+        println!("Settings reloaded.");
+    }
+
+    // 今の設定のウィンドウの大きさ。配置を変えた設定を読み込み直すと変わる
+    pub fn window_size(&self) -> [u32; 2] {
+        self.settings.window_size
+    }
+
+    fn save_replay(&self) {
+        // 途中から再開したゲームなど、最初からの操作が分からない場合は保存しない
+        let directions = match self.board.directions() {
+            Some(directions) => directions,
+            None => return,
+        };
+
+        let replay = Replay::new(&self.settings, self.board.game(), directions);
+        match replay.save_to_replay_folder() {
+            Ok(path) => println!("Replay saved to {}", path.display()),
+            Err(e) => println!("WARNING: Failed to save replay: {}", e),
+        }
+    }
+
+    // 途中のゲームを保存する。終わったゲームの場合は保存ファイルを消す
+    pub fn save_game(&self) {
+        // 再開の確認中、リプレイの再生中は、前回の保存データを上書きしない
+        if self.resume_offer.is_some() || self.playback.is_some() {
+            return;
+        }
+
+        if self.board.game().is_game_over() {
+            SavedGame::remove();
+        } else {
+            SavedGame::from_board(&self.board).save();
+        }
     }
 
     pub fn key_press(&mut self, args: &Button) {
         use piston_window::Button::Keyboard;
 
-        // キー↑ を入力
-        if *args == Keyboard(Key::Up) {
-            self.board.merge_from_bottom_to_top();
+        let key = match *args {
+            Keyboard(key) => key,
+            // 左ボタンのドラッグはスワイプとして扱う
+            Button::Mouse(MouseButton::Left) => {
+                self.swipe.press();
+                return;
+            },
+            _ => return,
+        };
+        let action = self.settings.key_bindings.action(key);
+
+        // 終了はいつでも受け付ける
+        if action == Some(Action::Quit) {
+            self.quit_requested = true;
+            return;
         }
-        // キー↓ を入力
-        if *args == Keyboard(Key::Down) {
-            self.board.merge_from_top_to_bottom();
+
+        // 再開の確認中は、続ける操作 (再開) と新しいゲームの操作だけ受け付ける
+        if let Some(saved) = self.resume_offer.take() {
+            if action == Some(Action::KeepGoing) {
+                if let Some(game) = saved.to_game_state() {
+                    self.board = Board::from_game(self.settings.clone(), game, saved.directions(), saved.keep_going(), saved.undo_count());
+                    self.replay_saved = false;
+                    self.bot_played = false;
+                }
+            } else if action != Some(Action::Restart) {
+                self.resume_offer = Some(saved);
+            }
+            return;
         }
-        // キー→ を入力
-        if *args == Keyboard(Key::Right) {
-            self.board.merge_from_left_to_right();
+
+        if let Some(action) = action {
+            self.handle_action(action);
+        }
+    }
+
+    pub fn mouse_move(&mut self, position: [f64; 2]) {
+        self.swipe.motion(position);
+    }
+
+    // タッチパッドの2本指のスワイプ。スクロールの移動量をスワイプと同じように扱う
+    pub fn mouse_scroll(&mut self, delta: [f64; 2]) {
+        let direction = match self.swipe.scroll(delta) {
+            Some(direction) => direction,
+            None => return,
+        };
+        if self.resume_offer.is_some() {
+            return;
+        }
+
+        self.handle_action(Action::from_direction(direction));
+    }
+
+    // ボタンを離した時。スワイプした向きのキーを押したのと同じように動かす
+    pub fn release(&mut self, args: &Button) {
+        if *args != Button::Mouse(MouseButton::Left) {
+            return;
+        }
+        let direction = match self.swipe.release() {
+            Some(direction) => direction,
+            None => return,
+        };
+        if self.resume_offer.is_some() {
+            return;
+        }
+
+        self.handle_action(Action::from_direction(direction));
+    }
+
+    fn handle_action(&mut self, action: Action) {
+        if self.playback.is_some() {
+            self.playback_action(action);
+            return;
+        }
+
+        // ヒントは次の操作で消える
+        self.hint = None;
+
+        match action {
+            // 上下左右に動かす
+            Action::Up => self.input_move(Direction::Up),
+            Action::Down => self.input_move(Direction::Down),
+            Action::Left => self.input_move(Direction::Left),
+            Action::Right => self.input_move(Direction::Right),
+            // 一つ前の盤面に戻す
+            Action::Undo => {
+                self.pending_directions.clear();
+                self.board.undo();
+            },
+            // 元に戻した盤面をやり直す
+            Action::Redo => {
+                self.pending_directions.clear();
+                self.board.redo();
+            },
+            // AIがおすすめする向きを表示
+            Action::Hint => self.hint = ai::best_direction(self.board.game(), HINT_DEPTH),
+            // 勝った後もプレイを続ける
+            Action::KeepGoing => {
+                if self.board.is_won() {
+                    self.board.keep_going();
+                }
+            },
+            // 自動プレイを切り替える。一時停止は自動プレイを止める
+            Action::Autoplay => {
+                self.autoplay = !self.autoplay;
+                self.pending_directions.clear();
+            },
+            Action::Pause => self.autoplay = false,
+            // 自動プレイの戦略を切り替える
+            Action::Strategy => self.strategy_index = (self.strategy_index + 1) % self.strategies.len(),
+            // 新しいゲームを始める。途中のゲームもそこまでのリプレイを残す
+            Action::Restart => {
+                if !self.replay_saved && self.board.game().moves() > 0 {
+                    self.save_replay();
+                }
+                self.board = Board::new(self.settings.clone(), App::new_rng(self.seed));
+                self.replay_saved = false;
+                self.bot_played = false;
+                self.pending_directions.clear();
+            },
+            Action::Quit => {},
         }
-        // キー← を入力
-        if *args == Keyboard(Key::Left) {
-            self.board.merge_from_right_to_left();
+    }
+
+    // 終了の操作をした場合
+    pub fn should_quit(&self) -> bool {
+        self.quit_requested
+    }
+
+    // アニメーション中の入力は、設定に応じてアニメーションを終わらせてすぐ動かすか、順番待ちにする
+    fn input_move(&mut self, direction: Direction) {
+        if self.board.is_locking() || !self.pending_directions.is_empty() {
+            if self.settings.snap_animations {
+                self.board.finish_animations();
+                while let Some(pending) = self.pending_directions.pop_front() {
+                    self.board.apply_move(pending);
+                    self.board.finish_animations();
+                }
+            } else {
+                // 順番待ちがいっぱいの場合は捨てる
+                if self.pending_directions.len() < self.settings.input_queue_size.max(0) as usize {
+                    self.pending_directions.push_back(direction);
+                }
+                return;
+            }
         }
+        self.board.apply_move(direction);
+    }
 
-        // キーspaceを入力
-        if *args == Keyboard(Key::Space) {
-            self.board = Board::new(self.settings);
+    // リプレイの再生中の操作
+    fn playback_action(&mut self, action: Action) {
+        let playback = self.playback.as_mut().unwrap();
+        match action {
+            // 一時停止・再開
+            Action::Pause => playback.toggle_pause(),
+            // 再生速度を変える
+            Action::Up => playback.faster(),
+            Action::Down => playback.slower(),
+            // 1手進める。アニメーション中のタイルは止めてから動かす
+            Action::Right => {
+                if let Some(direction) = playback.step_forward() {
+                    self.board.finish_animations();
+                    self.board.apply_move(direction);
+                }
+            },
+            // 1手戻す
+            Action::Left => {
+                if let Some(game) = playback.step_back() {
+                    self.board = Board::from_game(self.settings.clone(), game, Vec::new(), true, 0);
+                }
+            },
+            _ => {},
         }
     }
 
-}
\ No newline at end of file
+}
@@ -0,0 +1,52 @@
+use std::fs::{self, File};
+use std::io::Write;
+use crate::settings::path_beside_settings;
+
+static BEST_SCORE_FILENAME: &str = "best_score.txt";
+
+// 再起動しても残るベストスコア
+pub struct BestScore {
+    score: i32,
+}
+
+impl BestScore {
+    pub fn load() -> BestScore {
+        // ファイルが読めない場合は0から始める
+        let score = path_beside_settings(BEST_SCORE_FILENAME)
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|text| text.trim().parse::<i32>().ok())
+            .unwrap_or(0);
+
+        BestScore {
+            score: score,
+        }
+    }
+
+    pub fn score(&self) -> i32 {
+        self.score
+    }
+
+    // 現在のスコアがベストスコアを超えた場合、更新して保存する
+    pub fn update(&mut self, score: i32) {
+        if score > self.score {
+            self.score = score;
+            self.save();
+        }
+    }
+
+    fn save(&self) {
+        let path = match path_beside_settings(BEST_SCORE_FILENAME) {
+            Some(path) => path,
+            None => {
+                println!("WARNING: Failed to save best score: can't find exe path.");
+                return;
+            }
+        };
+
+        let result = File::create(&path)
+            .and_then(|mut file| write!(file, "{}", self.score));
+        if let Err(e) = result {
+            println!("WARNING: Failed to save best score: {}", e);
+        }
+    }
+}
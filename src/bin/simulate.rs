@@ -0,0 +1,300 @@
+// 戦略の強さを測るため、ウィンドウなしでゲームをまとめて実行する
+//
+// 使い方:
+//   simulate --strategy expectimax --games 100 --seed 1 --threads 4 --format json --output result.json
+#![allow(clippy::redundant_field_names)]
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::File;
+use std::io::{self, Write};
+use std::process;
+use std::sync::{Arc, Mutex, atomic::{AtomicUsize, Ordering}};
+use std::thread;
+use std::time::Instant;
+
+use serde::Serialize;
+use rust_2048_clone::{game::{check_board_size, GameState}, rng::GameRng, strategy::{strategy_by_name, STRATEGY_NAMES}};
+
+static USAGE: &str = "Usage: simulate [options]
+  --strategy <name>   random, corner or expectimax (default: corner)
+  --games <n>         number of games (default: 100)
+  --seed <n>          seed of the first game; game i uses seed + i (default: 1)
+  --threads <n>       worker threads (default: available cores)
+  --width <n>         board width (default: 4)
+  --height <n>        board height (default: 4)
+  --target <n>        tile that counts as a win (default: 2048)
+  --format <f>        csv or json (default: csv)
+  --output <path>     write results to a file instead of stdout";
+
+struct Options {
+    strategy: String,
+    games: usize,
+    seed: u64,
+    threads: usize,
+    width: i32,
+    height: i32,
+    target: i32,
+    format: String,
+    output: Option<String>,
+}
+
+// 1ゲームの結果
+#[derive(Serialize)]
+struct GameResult {
+    seed: u64,
+    score: i32,
+    max_tile: i32,
+    moves: i32,
+}
+
+fn main() {
+    let options = parse_options().unwrap_or_else(|message| {
+        eprintln!("{}\n\n{}", message, USAGE);
+        process::exit(2);
+    });
+
+    let started = Instant::now();
+    let mut results = run_games(&options);
+    let elapsed = started.elapsed().as_secs_f64();
+    results.sort_by_key(|result| result.seed);
+
+    let text = match options.format.as_str() {
+        "json" => to_json(&options, &results, elapsed),
+        _ => to_csv(&results, options.target),
+    };
+
+    let written = match options.output {
+        Some(ref path) => File::create(path).and_then(|mut file| file.write_all(text.as_bytes())),
+        None => io::stdout().write_all(text.as_bytes()),
+    };
+    if let Err(e) = written {
+        eprintln!("Failed to write results: {}", e);
+        process::exit(1);
+    }
+
+    // 集計は標準エラーに出す
+    eprintln!("{}", summary(&options, &results, elapsed));
+}
+
+fn parse_options() -> Result<Options, String> {
+    let mut options = Options {
+        strategy: "corner".to_string(),
+        games: 100,
+        seed: 1,
+        threads: thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+        width: 4,
+        height: 4,
+        target: 2048,
+        format: "csv".to_string(),
+        output: None,
+    };
+
+    let args: Vec<String> = env::args().skip(1).collect();
+    let mut i = 0;
+    while i < args.len() {
+        let name = args[i].as_str();
+        if name == "--help" || name == "-h" {
+            println!("{}", USAGE);
+            process::exit(0);
+        }
+        let value = args.get(i + 1).ok_or_else(|| format!("Missing value for {}", name))?;
+        let invalid = || format!("Invalid value for {}: '{}'", name, value);
+
+        match name {
+            "--strategy" => options.strategy = value.clone(),
+            "--games" => options.games = value.parse().map_err(|_| invalid())?,
+            "--seed" => options.seed = value.parse().map_err(|_| invalid())?,
+            "--threads" => options.threads = value.parse().map_err(|_| invalid())?,
+            "--width" => options.width = value.parse().map_err(|_| invalid())?,
+            "--height" => options.height = value.parse().map_err(|_| invalid())?,
+            "--target" => options.target = value.parse().map_err(|_| invalid())?,
+            "--format" => options.format = value.clone(),
+            "--output" => options.output = Some(value.clone()),
+            _ => return Err(format!("Unknown option {}", name)),
+        }
+        i += 2;
+    }
+
+    if !STRATEGY_NAMES.contains(&options.strategy.as_str()) {
+        return Err(format!("Unknown strategy '{}'", options.strategy));
+    }
+    if options.format != "csv" && options.format != "json" {
+        return Err(format!("Unknown format '{}'", options.format));
+    }
+    check_board_size(options.width, options.height)?;
+    // 設定の target_tile と同じく 2 以上の2の累乗
+    if options.target < 2 || options.target.count_ones() != 1 {
+        return Err(format!("Invalid value for --target: {} is not a power of two (2, 4, 8, ...)", options.target));
+    }
+    // 0 ゲームでは集計できない
+    if options.games == 0 {
+        return Err("Invalid value for --games: at least one game is needed".to_string());
+    }
+    // ゲーム i のシードは seed + i なので、最後のゲームまで u64 に収まる必要がある
+    if options.seed.checked_add(options.games as u64 - 1).is_none() {
+        return Err(format!("Seeds from {} for {} games don't fit in 64 bits", options.seed, options.games));
+    }
+    options.threads = options.threads.max(1);
+    Ok(options)
+}
+
+// ゲームをスレッドに分けて実行する。各スレッドは次のゲームの番号を取って進める
+fn run_games(options: &Options) -> Vec<GameResult> {
+    let next = Arc::new(AtomicUsize::new(0));
+    let results = Arc::new(Mutex::new(Vec::with_capacity(options.games)));
+
+    let workers: Vec<_> = (0..options.threads).map(|_| {
+        let next = Arc::clone(&next);
+        let results = Arc::clone(&results);
+        let (games, first_seed, width, height) = (options.games, options.seed, options.width, options.height);
+        let strategy = options.strategy.clone();
+
+        thread::spawn(move || {
+            loop {
+                let i = next.fetch_add(1, Ordering::SeqCst);
+                if i >= games {
+                    break;
+                }
+                let result = play_game(&strategy, first_seed + i as u64, width, height);
+                results.lock().unwrap().push(result);
+            }
+        })
+    }).collect();
+
+    for worker in workers {
+        worker.join().expect("simulation thread panicked");
+    }
+
+    Arc::try_unwrap(results).ok().unwrap().into_inner().unwrap()
+}
+
+fn play_game(strategy: &str, seed: u64, width: i32, height: i32) -> GameResult {
+    let strategy = strategy_by_name(strategy, seed).unwrap();
+    let mut game = GameState::new(width, height, GameRng::new(seed));
+
+    while !game.is_game_over() {
+        let direction = strategy.choose(&game);
+        // 動かない向きを選び続けると終わらないため、そこで打ち切る
+        if !game.apply_move(direction).moved {
+            break;
+        }
+    }
+
+    GameResult {
+        seed: seed,
+        score: game.score(),
+        max_tile: game.max_tile(),
+        moves: game.moves(),
+    }
+}
+
+fn to_csv(results: &[GameResult], target: i32) -> String {
+    let mut text = "seed,score,max_tile,moves,won\n".to_string();
+    for result in results.iter() {
+        text += &format!("{},{},{},{},{}\n",
+            result.seed, result.score, result.max_tile, result.moves, result.max_tile >= target);
+    }
+    text
+}
+
+// JSON で出力する全体の結果
+#[derive(Serialize)]
+struct JsonReport<'a> {
+    strategy: &'a str,
+    width: i32,
+    height: i32,
+    target: i32,
+    games: usize,
+    mean_score: f64,
+    median_score: f64,
+    win_rate: f64,
+    moves_per_second: f64,
+    max_tiles: &'a BTreeMap<i32, usize>,
+    results: Vec<JsonGameResult<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonGameResult<'a> {
+    #[serde(flatten)]
+    result: &'a GameResult,
+    won: bool,
+}
+
+fn to_json(options: &Options, results: &[GameResult], elapsed: f64) -> String {
+    let stats = Stats::new(results, options.target, elapsed);
+    let report = JsonReport {
+        strategy: &options.strategy,
+        width: options.width,
+        height: options.height,
+        target: options.target,
+        games: results.len(),
+        mean_score: stats.mean_score,
+        median_score: stats.median_score,
+        win_rate: stats.win_rate,
+        moves_per_second: stats.moves_per_second,
+        max_tiles: &stats.max_tiles,
+        results: results.iter()
+            .map(|result| JsonGameResult { result: result, won: result.max_tile >= options.target })
+            .collect(),
+    };
+    serde_json::to_string(&report).expect("simulation results are always serializable") + "\n"
+}
+
+fn summary(options: &Options, results: &[GameResult], elapsed: f64) -> String {
+    let stats = Stats::new(results, options.target, elapsed);
+    let mut text = format!("{} games with '{}' on {}x{} in {:.2}s\n",
+        results.len(), options.strategy, options.width, options.height, elapsed);
+    text += &format!("  mean score:   {:.2}\n", stats.mean_score);
+    text += &format!("  median score: {:.1}\n", stats.median_score);
+    text += &format!("  win rate:     {:.2}% (>= {})\n", stats.win_rate * 100.0, options.target);
+    text += &format!("  moves/sec:    {:.1}\n", stats.moves_per_second);
+    text += "  max tiles:\n";
+    for (tile, count) in stats.max_tiles.iter() {
+        text += &format!("    {:>6}: {}\n", tile, count);
+    }
+    text
+}
+
+fn median(sorted: &[i32]) -> f64 {
+    let n = sorted.len();
+    if n == 0 {
+        0.0
+    } else if n % 2 == 1 {
+        sorted[n / 2] as f64
+    } else {
+        (sorted[n / 2 - 1] + sorted[n / 2]) as f64 / 2.0
+    }
+}
+
+// 結果の集計
+struct Stats {
+    mean_score: f64,
+    median_score: f64,
+    win_rate: f64,
+    moves_per_second: f64,
+    // 最大のタイルごとのゲーム数
+    max_tiles: BTreeMap<i32, usize>,
+}
+
+impl Stats {
+    fn new(results: &[GameResult], target: i32, elapsed: f64) -> Stats {
+        let count = results.len().max(1) as f64;
+        let mut scores: Vec<i32> = results.iter().map(|result| result.score).collect();
+        scores.sort_unstable();
+
+        let mut max_tiles = BTreeMap::new();
+        for result in results.iter() {
+            *max_tiles.entry(result.max_tile).or_insert(0) += 1;
+        }
+        let total_moves: i64 = results.iter().map(|result| result.moves as i64).sum();
+
+        Stats {
+            mean_score: scores.iter().map(|&score| score as f64).sum::<f64>() / count,
+            median_score: median(&scores),
+            win_rate: results.iter().filter(|result| result.max_tile >= target).count() as f64 / count,
+            moves_per_second: if elapsed > 0.0 { total_moves as f64 / elapsed } else { 0.0 },
+            max_tiles: max_tiles,
+        }
+    }
+}
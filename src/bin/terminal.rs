@@ -0,0 +1,153 @@
+// ウィンドウを使わず、端末で遊ぶ。SSH 越しなど画面がない環境向け
+//
+// 使い方:
+//   terminal [--seed <n>]
+// 操作: 矢印キー / WASD で移動、U で元に戻す、R でやり直す、C で続行、Space で新しいゲーム、Q / Esc で終了
+#![allow(clippy::redundant_field_names)]
+
+use std::env;
+use std::io::{self, Write};
+use std::process;
+use std::rc::Rc;
+
+use termion::{clear, color, cursor, event::Key, input::TermRead, raw::IntoRawMode, screen::IntoAlternateScreen, style};
+use rust_2048_clone::{best_score::BestScore, board::Board, game::Direction, rng::GameRng, settings::Settings};
+
+// 1マスの文字数 (横) と行数 (縦)
+static CELL_WIDTH: usize = 8;
+static CELL_HEIGHT: usize = 3;
+
+fn main() {
+    let seed = parse_seed();
+    let settings = Rc::new(Settings::load());
+
+    if let Err(e) = run(&settings, seed) {
+        eprintln!("Terminal error: {}", e);
+        process::exit(1);
+    }
+}
+
+fn parse_seed() -> Option<u64> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.iter().position(|arg| arg == "--seed") {
+        Some(i) => match args.get(i + 1).map(|value| value.parse::<u64>()) {
+            Some(Ok(seed)) => Some(seed),
+            _ => {
+                eprintln!("Usage: terminal [--seed <n>]");
+                process::exit(2);
+            },
+        },
+        None => None,
+    }
+}
+
+fn new_rng(seed: Option<u64>) -> GameRng {
+    match seed {
+        Some(seed) => GameRng::new(seed),
+        None => GameRng::from_entropy(),
+    }
+}
+
+fn run(settings: &Rc<Settings>, seed: Option<u64>) -> io::Result<()> {
+    let mut stdout = io::stdout().into_raw_mode()?.into_alternate_screen()?;
+    let mut best_score = BestScore::load();
+    let mut board = Board::new(settings.clone(), new_rng(seed));
+    // 端末ではアニメーションしない
+    board.finish_animations();
+
+    write!(stdout, "{}", cursor::Hide)?;
+    render(&mut stdout, settings, &board, &best_score)?;
+
+    for key in io::stdin().keys() {
+        let direction = match key? {
+            Key::Up | Key::Char('w') | Key::Char('k') => Some(Direction::Up),
+            Key::Down | Key::Char('s') | Key::Char('j') => Some(Direction::Down),
+            Key::Left | Key::Char('a') | Key::Char('h') => Some(Direction::Left),
+            Key::Right | Key::Char('d') | Key::Char('l') => Some(Direction::Right),
+            Key::Char('u') => {
+                board.undo();
+                None
+            },
+            Key::Char('r') => {
+                board.redo();
+                None
+            },
+            Key::Char('c') => {
+                if board.is_won() {
+                    board.keep_going();
+                }
+                None
+            },
+            Key::Char(' ') => {
+                board = Board::new(settings.clone(), new_rng(seed));
+                board.finish_animations();
+                None
+            },
+            Key::Char('q') | Key::Esc | Key::Ctrl('c') => break,
+            _ => None,
+        };
+
+        if let Some(direction) = direction {
+            board.apply_move(direction);
+            board.finish_animations();
+        }
+        best_score.update(board.game().score());
+        render(&mut stdout, settings, &board, &best_score)?;
+    }
+
+    write!(stdout, "{}{}", style::Reset, cursor::Show)?;
+    stdout.flush()
+}
+
+// 設定の色 (0.0 - 1.0) を端末の24ビットカラーに変換する
+fn rgb(c: [f32; 3]) -> color::Rgb {
+    let channel = |v: f32| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+    color::Rgb(channel(c[0]), channel(c[1]), channel(c[2]))
+}
+
+fn render<W: Write>(out: &mut W, settings: &Settings, board: &Board, best_score: &BestScore) -> io::Result<()> {
+    let game = board.game();
+    let background = color::Bg(rgb(settings.tile_background_color));
+    // 端末は raw モードのため、改行は \r\n で書く
+    let line_width = game.width() as usize * (CELL_WIDTH + 1) + 1;
+
+    write!(out, "{}{}{}", style::Reset, clear::All, cursor::Goto(1, 1))?;
+    write!(out, "{}2048{}   SCORE {}   BEST {}\r\n\r\n",
+        style::Bold, style::Reset, game.score(), best_score.score().max(game.score()))?;
+
+    // タイルの上下と左右に背景色の隙間を入れる
+    let gap_line = format!("{}{}{}\r\n", background, " ".repeat(line_width), style::Reset);
+    write!(out, "{}", gap_line)?;
+    for y in 0..game.height() {
+        for line in 0..CELL_HEIGHT {
+            write!(out, "{} ", background)?;
+            for x in 0..game.width() {
+                let score = game.get(x, y);
+                let text = if score != 0 && line == CELL_HEIGHT / 2 { score.to_string() } else { String::new() };
+                let cell_color = if score == 0 { settings.tiles_colors[0] } else { settings.tile_color(score) };
+
+                write!(out, "{}{}{}{:^width$}{}{} ",
+                    color::Bg(rgb(cell_color)),
+                    color::Fg(rgb(settings.tile_text_color(score))),
+                    style::Bold,
+                    text,
+                    style::Reset,
+                    background,
+                    width = CELL_WIDTH)?;
+            }
+            write!(out, "{}\r\n", style::Reset)?;
+        }
+        write!(out, "{}", gap_line)?;
+    }
+
+    write!(out, "\r\n")?;
+    if board.is_won() {
+        write!(out, "YOU WIN!  C: keep going   Space: new game\r\n")?;
+    } else if board.is_game_over() {
+        write!(out, "GAME OVER  Space: new game   U: undo\r\n")?;
+    } else {
+        write!(out, "\r\n")?;
+    }
+    write!(out, "Arrows/WASD: move   U: undo   R: redo   Space: new game   Q: quit\r\n")?;
+    out.flush()
+}
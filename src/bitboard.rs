@@ -0,0 +1,277 @@
+use std::sync::OnceLock;
+use crate::game::{Direction, GameState};
+
+// 4x4の盤面を64ビットに詰めた表現
+//
+// 1マス4ビットで、タイルのスコアの2の何乗か (ランク) を持つ。0は空きマス
+// マス (x, y) はビット 16 * y + 4 * x から始まる。1行が16ビットになるため、
+// 全ての行のパターン (65536通り) の動かした結果とスコアを表にしておき、行ごとに引く
+// 上下の移動は盤面を転置して左右の表を使う
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Bitboard(pub u64);
+
+// 1マスに入る最大のランク (32768)。これ同士をマージする移動は表せない
+pub static MAX_RANK: u8 = 15;
+
+struct RowTables {
+    left: Vec<u16>,
+    right: Vec<u16>,
+    score_left: Vec<u32>,
+    score_right: Vec<u32>,
+    // MAX_RANK 同士がマージされる行。左右どちらに動かしてもマージされる
+    overflow: Vec<bool>,
+}
+
+static ROW_TABLES: OnceLock<RowTables> = OnceLock::new();
+
+fn row_tables() -> &'static RowTables {
+    ROW_TABLES.get_or_init(|| {
+        let mut tables = RowTables {
+            left: vec![0; 65536],
+            right: vec![0; 65536],
+            score_left: vec![0; 65536],
+            score_right: vec![0; 65536],
+            overflow: vec![false; 65536],
+        };
+
+        for row in 0..65536usize {
+            let (left, score, overflow) = slide_row_left(row as u16);
+            tables.left[row] = left;
+            tables.score_left[row] = score;
+            tables.overflow[row] = overflow;
+
+            // 右に動かすのは、左右を反転して左に動かすのと同じ
+            let (reversed, score, _) = slide_row_left(reverse_row(row as u16));
+            tables.right[row] = reverse_row(reversed);
+            tables.score_right[row] = score;
+        }
+        tables
+    })
+}
+
+// 1行を左に詰めてマージする。GameState::slide と同じく、1回の移動で同じタイルは1度しかマージしない
+// MAX_RANK 同士はマージできないため、その場合は3つ目の値を true にする
+fn slide_row_left(row: u16) -> (u16, u32, bool) {
+    let mut out = [0u8; 4];
+    let mut score = 0;
+    let mut target = 0;
+    let mut can_merge = false;
+    let mut overflow = false;
+
+    for i in 0..4 {
+        let rank = ((row >> (4 * i)) & 0xf) as u8;
+        if rank == 0 {
+            continue;
+        }
+        if can_merge && out[target - 1] == rank && rank == MAX_RANK {
+            overflow = true;
+        }
+        if can_merge && out[target - 1] == rank && rank < MAX_RANK {
+            out[target - 1] += 1;
+            score += 1u32 << (rank + 1);
+            can_merge = false;
+        } else {
+            out[target] = rank;
+            target += 1;
+            can_merge = true;
+        }
+    }
+
+    let packed = out.iter().enumerate().fold(0u16, |acc, (i, &rank)| acc | (rank as u16) << (4 * i));
+    (packed, score, overflow)
+}
+
+fn reverse_row(row: u16) -> u16 {
+    (row >> 12) | ((row >> 4) & 0x00f0) | ((row << 4) & 0x0f00) | (row << 12)
+}
+
+// 4x4の行列として転置する
+fn transpose(x: u64) -> u64 {
+    let a1 = x & 0xf0f0_0f0f_f0f0_0f0f;
+    let a2 = x & 0x0000_f0f0_0000_f0f0;
+    let a3 = x & 0x0f0f_0000_0f0f_0000;
+    let a = a1 | (a2 << 12) | (a3 >> 12);
+    let b1 = a & 0xff00_ff00_00ff_00ff;
+    let b2 = a & 0x00ff_00ff_0000_0000;
+    let b3 = a & 0x0000_0000_ff00_ff00;
+    b1 | (b2 >> 24) | (b3 << 24)
+}
+
+impl Bitboard {
+    // 4x4で、全てのタイルが 2 から 32768 の2の累乗の場合だけ変換できる
+    pub fn from_game(game: &GameState) -> Option<Bitboard> {
+        if game.width() != 4 || game.height() != 4 {
+            return None;
+        }
+
+        let mut board = 0u64;
+        for (i, &score) in game.grid().iter().enumerate() {
+            if score == 0 {
+                continue;
+            }
+            if score < 2 || score.count_ones() != 1 || score.trailing_zeros() > MAX_RANK as u32 {
+                return None;
+            }
+            board |= (score.trailing_zeros() as u64) << (4 * i);
+        }
+        Some(Bitboard(board))
+    }
+
+    // 行優先のスコアの並び (GameState::grid と同じ形)
+    pub fn to_grid(self) -> Vec<i32> {
+        (0..16).map(|i| self.score_at(i)).collect()
+    }
+
+    pub fn rank(self, x: i32, y: i32) -> u8 {
+        ((self.0 >> (16 * y + 4 * x)) & 0xf) as u8
+    }
+
+    pub fn get(self, x: i32, y: i32) -> i32 {
+        self.score_at((y * 4 + x) as usize)
+    }
+
+    fn score_at(self, i: usize) -> i32 {
+        let rank = (self.0 >> (4 * i)) & 0xf;
+        if rank == 0 { 0 } else { 1 << rank }
+    }
+
+    pub fn empty_cells(self) -> Vec<(i32, i32)> {
+        (0..16)
+            .filter(|&i| (self.0 >> (4 * i)) & 0xf == 0)
+            .map(|i| (i % 4, i / 4))
+            .collect()
+    }
+
+    // 空きマスにタイルを置く
+    pub fn with_tile(self, x: i32, y: i32, score: i32) -> Bitboard {
+        let shift = 16 * y + 4 * x;
+        let rank = score.trailing_zeros() as u64;
+        Bitboard((self.0 & !(0xf << shift)) | (rank << shift))
+    }
+
+    // 一番大きいタイルのランク
+    pub fn max_rank(self) -> u8 {
+        (0..16).map(|i| ((self.0 >> (4 * i)) & 0xf) as u8).max().unwrap_or(0)
+    }
+
+    // タイルを動かした盤面と、増えたスコア。タイルは生成しない
+    // 32768 同士がマージされて、1マス4ビットで表せないタイルができる場合は None
+    pub fn slide(self, direction: Direction) -> Option<(Bitboard, i32)> {
+        let tables = row_tables();
+        let rows = |board: u64, table: &[u16], scores: &[u32]| -> Option<(u64, u32)> {
+            let mut result = 0u64;
+            let mut score = 0;
+            for y in 0..4 {
+                let row = ((board >> (16 * y)) & 0xffff) as usize;
+                if tables.overflow[row] {
+                    return None;
+                }
+                result |= (table[row] as u64) << (16 * y);
+                score += scores[row];
+            }
+            Some((result, score))
+        };
+
+        let (board, score) = match direction {
+            Direction::Left => rows(self.0, &tables.left, &tables.score_left)?,
+            Direction::Right => rows(self.0, &tables.right, &tables.score_right)?,
+            Direction::Up => {
+                let (board, score) = rows(transpose(self.0), &tables.left, &tables.score_left)?;
+                (transpose(board), score)
+            },
+            Direction::Down => {
+                let (board, score) = rows(transpose(self.0), &tables.right, &tables.score_right)?;
+                (transpose(board), score)
+            },
+        };
+        Some((Bitboard(board), score as i32))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng, XorShiftRng};
+
+    fn random_game(rng: &mut XorShiftRng) -> GameState {
+        let grid: Vec<i32> = (0..16)
+            .map(|_| match rng.gen_range(0, 14) {
+                rank if rank < 3 => 0,
+                rank => 1 << (rank - 2),
+            })
+            .collect();
+        let rows: Vec<&[i32]> = grid.chunks(4).collect();
+        GameState::from_rows(&rows)
+    }
+
+    #[test]
+    fn slide_matches_game_state() {
+        let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+        for _ in 0..5000 {
+            let game = random_game(&mut rng);
+            let board = Bitboard::from_game(&game).unwrap();
+            assert_eq!(board.to_grid(), game.grid());
+
+            for &direction in Direction::ALL.iter() {
+                let mut expected = game.clone();
+                let outcome = expected.slide(direction);
+                let (slid, score) = board.slide(direction).unwrap();
+
+                assert_eq!(slid.to_grid(), expected.grid(), "{:?} {:?}", direction, game.grid());
+                assert_eq!(score, outcome.score_gained);
+                assert_eq!(slid != board, outcome.moved);
+            }
+        }
+    }
+
+    #[test]
+    fn refuses_to_merge_past_the_largest_tile() {
+        let game = GameState::from_rows(&[
+            &[32768, 32768, 16384, 16384],
+            &[16384, 0, 0, 0],
+            &[16384, 0, 0, 0],
+            &[2, 0, 0, 0],
+        ]);
+        let board = Bitboard::from_game(&game).unwrap();
+        assert_eq!(board.max_rank(), MAX_RANK);
+
+        for &direction in Direction::ALL.iter() {
+            let mut expected = game.clone();
+            let outcome = expected.slide(direction);
+            match board.slide(direction) {
+                // 32768 同士がマージされない向きは GameState と同じ
+                Some((slid, score)) => {
+                    assert_eq!(slid.to_grid(), expected.grid(), "{:?}", direction);
+                    assert_eq!(score, outcome.score_gained);
+                },
+                // GameState は 65536 を作る
+                None => assert_eq!(expected.max_tile(), 65536, "{:?}", direction),
+            }
+        }
+        assert!(board.slide(Direction::Left).is_none());
+        assert!(board.slide(Direction::Up).is_some());
+    }
+
+    #[test]
+    fn transpose_swaps_rows_and_columns() {
+        let mut rng = XorShiftRng::from_seed([5, 6, 7, 8]);
+        for _ in 0..100 {
+            let board = Bitboard(rng.gen());
+            let transposed = Bitboard(transpose(board.0));
+            for y in 0..4 {
+                for x in 0..4 {
+                    assert_eq!(transposed.rank(x, y), board.rank(y, x));
+                }
+            }
+            assert_eq!(transpose(transposed.0), board.0);
+        }
+    }
+
+    #[test]
+    fn rejects_boards_it_cannot_represent() {
+        let game = GameState::from_rows(&[&[3; 4][..]; 4]);
+        assert_eq!(Bitboard::from_game(&game), None);
+        let game = GameState::from_rows(&[&[0; 5][..]; 3]);
+        assert_eq!(Bitboard::from_game(&game), None);
+    }
+}
@@ -1,61 +1,63 @@
 use std::collections::HashSet;
+use std::rc::Rc;
 
 use opengl_graphics::GlGraphics;
-use rand::random;
 use piston_window::*;
-use crate::{tile::{Tile, TileState}, settings::Settings, number_renderer::NumberRenderer};
+use crate::{game::{Direction, GameState, MoveOutcome}, history::History, rng::GameRng, tile::{Tile, TileState}, settings::Settings, number_renderer::NumberRenderer};
 
 fn rgb2rgba(c: [f32; 3]) -> [f32; 4] { [c[0], c[1], c[2], 1.0] }
 
-pub struct Board<'a> {
-    tiles: Vec<Tile<'a>>,
-    score: i32,
-    settings: &'a Settings,
+// 元に戻すために保存する盤面の最大数
+static HISTORY_SIZE: usize = 256;
+
+pub struct Board {
+    game: GameState,
+    history: History<GameState>,
+    // 盤面が変化した操作の向き。元に戻した後も、やり直せるように残しておく
+    directions: Vec<Direction>,
+    tiles: Vec<Tile>,
+    // 勝った後もプレイを続けるか
+    keep_going: bool,
+    // App と共有する設定。読み込み直した時は set_settings で差し替える
+    settings: Rc<Settings>,
 }
 
-impl<'a> Board<'a> {
-    pub fn new(settings: &Settings) -> Board {
-        let mut board = Board {
-            tiles: Vec::<Tile>::new(),
-            score: 0,
-            settings: settings,
-        };
-        board.generate_tile();
-        board.generate_tile();
-        board
+impl Board {
+    pub fn new(settings: Rc<Settings>, rng: GameRng) -> Board {
+        let game = GameState::new(settings.tile_width, settings.tile_height, rng);
+        Board::from_game(settings, game, Vec::new(), false, 0)
     }
 
-    pub fn generate_tile(&mut self) {
-        // タイルの枚数が最大枚数と等しい場合、タイル生成しない
-        if self.tiles.len() == (self.settings.tile_width * self.settings.tile_height) as usize {
-            return;
+    // 途中のゲームの状態と、そこまでの操作の向きからボードを作る
+    // undo_count は既に元に戻した回数。再開しても元に戻せる回数が増えないように引き継ぐ
+    pub fn from_game(settings: Rc<Settings>, game: GameState, directions: Vec<Direction>, keep_going: bool, undo_count: i32) -> Board {
+        let mut tiles = Vec::<Tile>::new();
+
+        // ゲームの初期状態からタイルを生成
+        for y in 0..game.height() {
+            for x in 0..game.width() {
+                let score = game.get(x, y);
+                if score != 0 {
+                    tiles.push(Tile::new(&settings, score, x, y));
+                }
+            }
         }
 
-        // タイル生成が可能な場所にタイル生成
-        loop {
-            // ランダムでタイルのx座標、y座標を初期化
-            let x = (random::<u32>() % self.settings.tile_width as u32) as i32;
-            let y = (random::<u32>() % self.settings.tile_height as u32) as i32;
-
-            // 初期化した座標にタイルがない場合
-            if self.get_tile(x, y).is_none() {
-                // ランダムでタイルのスコアを設定：後でやる
-                let score = if random::<u32>() % 10 == 0 {
-                    4
-                } else {
-                    2
-                };
-                // タイルを追加
-                self.tiles.push(Tile::new(self.settings, score, x, y));
-                break;
-            }
+        Board {
+            game: game,
+            // 元に戻せる回数は、ゲームを始めた時の設定で決まる
+            history: History::with_undo_count(HISTORY_SIZE, settings.undo_limit, undo_count),
+            directions: directions,
+            tiles: tiles,
+            keep_going: keep_going,
+            settings: settings,
         }
     }
 
     pub fn update(&mut self, dt: f64) {
         // タイルの状態と座標を更新
         for tile in self.tiles.iter_mut() {
-            tile.update(dt);
+            tile.update(&self.settings, dt);
         }
 
         // タイルを動かして、止まった後にマージの判定をするため
@@ -63,7 +65,6 @@ impl<'a> Board<'a> {
             return;
         }
 
-        let mut score_to_added = 0;
         // タイルが重なるか判定
         let mut tiles_need_removed = HashSet::<usize>::new();
         let mut tiles_need_added = Vec::<Tile>::new();
@@ -84,14 +85,13 @@ impl<'a> Board<'a> {
 
                 tiles_need_removed.insert(i);
                 tiles_need_removed.insert(j);
-                tiles_need_added.push(Tile::new_combined(self.settings, tile1.score + tile2.score, tile1.tile_x, tile1.tile_y));
-                score_to_added += tile1.score + tile2.score;
+                tiles_need_added.push(Tile::new_combined(&self.settings, tile1.score + tile2.score, tile1.tile_x, tile1.tile_y));
                 break;
             }
         }
 
         // 重なるタイルがある場合、タイルを削除
-        if tiles_need_removed.len() > 0 {
+        if !tiles_need_removed.is_empty() {
             let mut tiles = Vec::<Tile>::new();
 
             for i in 0..self.tiles.len() {
@@ -105,310 +105,160 @@ impl<'a> Board<'a> {
             } 
 
             self.tiles = tiles;
-            self.add_score(score_to_added);
         }
     }
 
     pub fn render(&self, number_renderer: &NumberRenderer, c: &Context, gl: &mut GlGraphics) {
-        // ボードを描画
-        number_renderer.render(
-            self.score as u32,
-            self.settings.best_rect[0] + self.settings.best_rect[2] / 2.0,
-            self.settings.best_rect[1] + self.settings.best_rect[3] / 2.0,
-            self.settings.best_rect[2], self.settings.text_light_color,
-            c, gl);
-
         // ボードを描画
         self.render_board(c, gl);
         // タイルを描画
         self.render_tiles(number_renderer, c, gl);
     }
 
-    pub fn merge_from_bottom_to_top(&mut self) {
-        let height = self.settings.tile_height;
-        self.merge_col(0, height, 1);
+    pub fn game(&self) -> &GameState {
+        &self.game
     }
 
-    pub fn merge_from_top_to_bottom(&mut self) {
-        let height = self.settings.tile_height;
-        self.merge_col(height - 1, -1, -1);
+    pub fn tiles(&self) -> &[Tile] {
+        &self.tiles
     }
 
-    fn merge_col(&mut self, y_start: i32, y_end: i32, y_step: i32) {
-        if self.is_locking() {
-            return;
-        }
+    // 読み込み直した設定に差し替える。ゲームとアニメーション中のタイルはそのまま続ける
+    // 盤面の大きさは変えられないため、呼ぶ側で確かめておく
+    pub fn set_settings(&mut self, settings: Rc<Settings>) {
+        self.settings = settings;
+    }
 
-        let mut need_generate = false;
-        let mut steps: Vec<i32> = Vec::with_capacity(self.settings.tile_width as usize);
-        let mut next_step = y_start;
+    // タイルが止まった後に、動かせるタイルがない場合
+    pub fn is_game_over(&self) -> bool {
+        !self.is_locking() && self.game.is_game_over()
+    }
 
-        // 上から下に動かす時
-        if y_step < 0 {
-            while next_step > y_end {
-                steps.push(next_step); next_step += y_step
-            } 
-        } // 下から上に動かす時
-        else {
-            while next_step < y_end {
-                steps.push(next_step); next_step += y_step
-            }
-        }
+    // タイルが止まった後に、目標のタイルに到達していて、まだ続行を選んでいない場合
+    pub fn is_won(&self) -> bool {
+        !self.keep_going && !self.is_locking() && self.game.max_tile() >= self.settings.target_tile
+    }
 
-        loop {
-            for col in 0..self.settings.tile_height {
-                for row in steps.to_vec() {
-                    match self.get_mut_tile(col, row) {
-                        None => {
-                            match self.get_mut_next_tile(col, row, 0, y_step) {
-                                Some (ref mut tile) => {
-                                    need_generate = true;
-                                    tile.start_moving(col, row);
-                                },
-                                _ => {},
-                            }
-                        },
-                        _ => {},
-                    }
-                }
-            }
+    // 勝った後もプレイを続ける。以降は勝ちの判定をしない
+    pub fn keep_going(&mut self) {
+        self.keep_going = true;
+    }
 
-            let mut did_merged = false;
-            for col in 0..self.settings.tile_height {
-                let mut found = false;
-                let mut sx = 0;
-                let mut sy = 0;
-                let mut dx = 0;
-                let mut dy = 0;
-                for row in steps.to_vec() {
-                    match self.get_tile(col, row) {
-                        Some(ref d_tile) => {
-                            match self.get_next_tile(col, row, 0, y_step) {
-                                Some(ref s_tile) 
-                                if d_tile.score == s_tile.score 
-                                && self.get_tile_count(d_tile.tile_x, d_tile.tile_y) == 1 => {
-                                    found = true;
-                                    dx = d_tile.tile_x;
-                                    dy = d_tile.tile_y;
-                                    sx = s_tile.tile_x;
-                                    sy = s_tile.tile_y;
-                                    break;
-                                },
-                                _ => {},
-                            }
-                        },
-                        None => {
-                            break;
-                        }
-                    }
-                }
+    // 最初から現在の盤面までの操作の向き。途中からしか分からない場合は None
+    pub fn directions(&self) -> Option<&[Direction]> {
+        self.directions.get(..self.game.moves() as usize)
+    }
 
-                if found {
-                    need_generate = true;
-                    did_merged = true;
-                    let tile = self.get_mut_tile(sx, sy).unwrap();
-                    tile.start_moving(dx, dy);
-                    println!("merge ({}, {}) to ({}, {})", sx, sy, dx, dy);
-                }
-            }
+    pub fn keeps_going(&self) -> bool {
+        self.keep_going
+    }
 
-            if !did_merged {
-                break;
-            }
-        }
+    pub fn undo_count(&self) -> i32 {
+        self.history.undo_count()
+    }
 
-        if need_generate {
-            self.generate_tile();
-        }
+    pub fn merge_from_bottom_to_top(&mut self) {
+        self.apply_move(Direction::Up);
+    }
+
+    pub fn merge_from_top_to_bottom(&mut self) {
+        self.apply_move(Direction::Down);
     }
 
     pub fn merge_from_left_to_right(&mut self) {
-        let width = self.settings.tile_width;
-        // 動かす先のタイルの検索に使う
-        self.merge_row(width - 1, -1, -1);
+        self.apply_move(Direction::Right);
     }
 
     pub fn merge_from_right_to_left(&mut self) {
-        let width = self.settings.tile_width;
-        self.merge_row(0, width - 1, 1)
+        self.apply_move(Direction::Left);
     }
 
-    fn merge_row(&mut self, x_start: i32, x_end: i32, x_step: i32) {
+    // 動かせない間は None
+    pub fn apply_move(&mut self, direction: Direction) -> Option<MoveOutcome> {
         // タイルのStatusがStaticでない場合、動かせない
-        if self.is_locking() {
-            return;
+        // 勝った後は続行を選ぶまで動かせない
+        if self.is_locking() || self.is_won() {
+            return None;
         }
 
-        let mut need_generate = false;
-        let mut steps: Vec<i32> = Vec::with_capacity(self.settings.tile_width as usize);
-        let mut next_step = x_start;
-
-        // 動かす先のタイルの検索に使う
-        // 左から右に動かす時
-        if x_step < 0 {
-            while next_step > x_end {
-                steps.push(next_step); next_step += x_step
-            } 
-        } // 右から左に動かす時
-        else {
-            while next_step < x_end {
-                steps.push(next_step); next_step += x_step
-            }
+        let before = self.game.clone();
+        let outcome = self.game.apply_move(direction);
+        // 盤面が変化した場合だけ、元に戻せるように保存する
+        if outcome.moved {
+            self.history.push(before);
+            // 元に戻した後の操作は、やり直し用に残していた向きを捨てる
+            self.directions.truncate(self.game.moves() as usize - 1);
+            self.directions.push(direction);
         }
 
-        // タイルを動かす
-        loop {
-            for row in 0..self.settings.tile_height {
-                for col in steps.to_vec() {
-                    // 動かす先に既にタイルが置いてあるか
-                    match self.get_mut_tile(col, row) {
-                        // まだタイルが置かれていない場合
-                        None => {
-                            // 動かす元のタイルがあるか
-                            match self.get_mut_next_tile(col, row, x_step, 0) {
-                                // 動かす元のタイルがある場合、タイルの座標を更新して、動かす
-                                Some (ref mut tile) => {
-                                    println!("move ({}, {}) to ({}, {})", tile.tile_x, tile.tile_y, col, row);
-                                    need_generate = true;
-                                    tile.start_moving(col, row);
-                                },
-                                // 動かす元のタイルがない場合、何もしない
-                                _ => {},
-                            }
-                        },
-                        // タイルが既に置かれている場合、何もしない
-                        _ => {},
-                    }
-                }
-            }
-
-            // タイルをマージする
-            let mut did_merged = false;
-            for row in 0..self.settings.tile_height {
-                let mut found = false;
-                let mut sx = 0;
-                let mut sy = 0;
-                let mut dx = 0;
-                let mut dy = 0;
-                for col in steps.to_vec() {
-                    match self.get_tile(col, row) {
-                        Some(ref d_tile) => {
-                            match self.get_next_tile(col, row, x_step, 0) {
-                                Some(ref s_tile) 
-                                if d_tile.score == s_tile.score 
-                                && self.get_tile_count(d_tile.tile_x, d_tile.tile_y) == 1 => {
-                                    found = true;
-                                    dx = d_tile.tile_x;
-                                    dy = d_tile.tile_y;
-                                    sx = s_tile.tile_x;
-                                    sy = s_tile.tile_y;
-                                    break;
-                                },
-                                _ => {},
-                            }
-                        },
-                        None => {
-                            break;
-                        }
-                    }
-                }
+        // 動かす前の座標でタイルを探しておく (動かした後のタイルと重ならないように)
+        let indices: Vec<Option<usize>> = outcome.moves.iter()
+            .map(|m| self.tiles.iter().position(|tile| tile.tile_x == m.from_x && tile.tile_y == m.from_y))
+            .collect();
 
-                if found {
-                    need_generate = true;
-                    did_merged = true;
-                    let tile = self.get_mut_tile(sx, sy).unwrap();
-                    tile.start_moving(dx, dy);
-                    println!("merge ({}, {}) to ({}, {})", sx, sy, dx, dy);
-                }
-            }
-
-            if !did_merged {
-                break;
+        // タイルを動かす。マージは止まった後に update で行う
+        for (m, index) in outcome.moves.iter().zip(indices) {
+            if let Some(i) = index {
+                self.tiles[i].start_moving(&self.settings, m.to_x, m.to_y);
             }
         }
 
-        if need_generate {
-            self.generate_tile();
+        // 生成されたタイルを追加
+        if let Some((x, y, score)) = outcome.spawned {
+            self.tiles.push(Tile::new(&self.settings, score, x, y));
         }
+        Some(outcome)
     }
 
-    fn is_locking(&self) -> bool {
-        for tile in self.tiles.iter() {
-            if tile.status != TileState::TileStatic {
-                return true;
-            }
+    // 一つ前の盤面に戻す。アニメーション中のタイルは止めて置き直す
+    pub fn undo(&mut self) {
+        if let Some(state) = self.history.undo(self.game.clone()) {
+            self.game = state;
+            self.reset_tiles();
         }
-        false
     }
 
-    fn get_next_tile<'b>(&'b self, x: i32, y: i32, step_x: i32, step_y: i32) -> Option<&'b Tile<'a>> {
-        let mut x = x + step_x;
-        let mut y = y + step_y;
-        while x >= 0 && x < self.settings.tile_width
-        && y >= 0 && y < self.settings.tile_height {
-            let tile = self.get_tile(x, y);
-            if tile.is_some() {
-                return tile;
-            }
-            x += step_x;
-            y += step_y;
+    // 元に戻した盤面をやり直す
+    pub fn redo(&mut self) {
+        if let Some(state) = self.history.redo(self.game.clone()) {
+            self.game = state;
+            self.reset_tiles();
         }
-        None
     }
 
-
-    fn get_mut_next_tile<'b>(&'b mut self, x: i32, y: i32, step_x: i32, step_y: i32) -> Option<&'b mut Tile<'a>> {
-        let mut x = x + step_x;
-        let mut y = y + step_y;
-        let mut found = false;
-        while x >= 0 && x < self.settings.tile_width
-        && y >= 0 && y < self.settings.tile_height {
-            let tile = self.get_tile(x, y);
-
-            if tile.is_some() {
-                found = true;
-                break;
-            }
-            x += step_x;
-            y += step_y;
-        }
-
-        if found {
-            self.get_mut_tile(x, y)
-        } else {
-            None
-        }
+    pub fn can_undo(&self) -> bool {
+        self.history.can_undo()
     }
-    
-    fn get_tile<'b>(&'b self, x: i32, y: i32) -> Option<&'b Tile<'a>> {
-        for tile in self.tiles.iter() {
-            if tile.tile_x == x && tile.tile_y == y {
-                return Some(tile);
+
+    // ゲームの状態に合わせてタイルを置き直す
+    fn reset_tiles(&mut self) {
+        self.tiles.clear();
+        for y in 0..self.game.height() {
+            for x in 0..self.game.width() {
+                let score = self.game.get(x, y);
+                if score != 0 {
+                    self.tiles.push(Tile::new_static(score, x, y));
+                }
             }
         }
-
-        None
     }
 
-    fn get_mut_tile<'b>(&'b mut self, x: i32, y: i32) -> Option<&'b mut Tile<'a>> {
-        for tile in self.tiles.iter_mut() {
-            if tile.tile_x == x && tile.tile_y == y {
-                return Some(tile);
-            }
+    // アニメーション中のタイルを全て止めて、マージを済ませる
+    // マージしたタイルはもう一度アニメーションするため、全て止まるまで繰り返す
+    pub fn finish_animations(&mut self) {
+        self.update(f64::MAX);
+        while self.is_locking() {
+            self.update(f64::MAX);
         }
-
-        None
     }
 
-    fn get_tile_count(&self, x: i32, y: i32) -> i32 {
-        let mut count = 0;
+    pub fn is_locking(&self) -> bool {
         for tile in self.tiles.iter() {
-            if tile.tile_x == x && tile.tile_y == y {
-                count += 1;
+            if tile.status != TileState::TileStatic {
+                return true;
             }
         }
-
-        count
+        false
     }
 
     fn render_board(&self, c: &Context, gl: &mut GlGraphics) {
@@ -451,13 +301,8 @@ impl<'a> Board<'a> {
 
     fn render_tiles(&self, number_renderer: &NumberRenderer, c: &Context, gl: &mut GlGraphics) {
         for tile in self.tiles.iter() {
-            tile.render(number_renderer, c, gl);
+            tile.render(&self.settings, number_renderer, c, gl);
         }
     }
 
-    fn add_score(&mut self, score: i32) {
-        self.score += score;
-        println!("Score: {}", self.score);
-    }
-
 }
\ No newline at end of file
@@ -0,0 +1,304 @@
+use rand::Rng;
+use crate::rng::GameRng;
+
+// タイルを動かす向き
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Direction {
+    pub const ALL: [Direction; 4] = [Direction::Up, Direction::Down, Direction::Left, Direction::Right];
+
+    // リプレイなどで使う1文字の表記
+    pub fn to_char(self) -> char {
+        match self {
+            Direction::Up => 'U',
+            Direction::Down => 'D',
+            Direction::Left => 'L',
+            Direction::Right => 'R',
+        }
+    }
+
+    pub fn from_char(c: char) -> Option<Direction> {
+        match c {
+            'U' => Some(Direction::Up),
+            'D' => Some(Direction::Down),
+            'L' => Some(Direction::Left),
+            'R' => Some(Direction::Right),
+            _ => None,
+        }
+    }
+}
+
+// 1枚のタイルの移動
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TileMove {
+    pub from_x: i32,
+    pub from_y: i32,
+    pub to_x: i32,
+    pub to_y: i32,
+    // 移動先のタイルとマージされるか
+    pub merged: bool,
+}
+
+// 1回の操作の結果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MoveOutcome {
+    // 盤面が変化したか
+    pub moved: bool,
+    pub moves: Vec<TileMove>,
+    pub score_gained: i32,
+    // 新しく生成されたタイル (x, y, score)
+    pub spawned: Option<(i32, i32, i32)>,
+}
+
+// 盤面の一辺の最大のマス数
+pub static MAX_BOARD_SIDE: i32 = 32;
+
+// 盤面の大きさとして使えるか確かめる。最初にタイルを2枚置くため、2マス以上必要
+pub fn check_board_size(width: i32, height: i32) -> Result<(), String> {
+    if width < 1 || height < 1 {
+        return Err(format!("board size {}x{} must be at least 1x1", width, height));
+    }
+    if width > MAX_BOARD_SIDE || height > MAX_BOARD_SIDE {
+        return Err(format!("board size {}x{} is larger than {}x{}", width, height, MAX_BOARD_SIDE, MAX_BOARD_SIDE));
+    }
+    if width * height < 2 {
+        return Err(format!("board size {}x{} has no room for the two starting tiles", width, height));
+    }
+    Ok(())
+}
+
+// 盤面で作れる最大のタイル。n マスの盤面では、4 が生成され続けても 2^(n+1) まで
+// スコアが i32 に収まるように 2^30 で打ち切る
+pub fn largest_possible_tile(width: i32, height: i32) -> i32 {
+    1 << (width * height + 1).min(30)
+}
+
+// 描画に依存しないゲームの状態
+#[derive(Debug, Clone)]
+pub struct GameState {
+    width: i32,
+    height: i32,
+    // 行優先で並べたタイルのスコア。0は空きマス
+    grid: Vec<i32>,
+    score: i32,
+    // 盤面が変化した操作の回数
+    moves: i32,
+    rng: GameRng,
+}
+
+impl GameState {
+    pub fn new(width: i32, height: i32, rng: GameRng) -> GameState {
+        let mut state = GameState {
+            width: width,
+            height: height,
+            grid: vec![0; (width * height) as usize],
+            score: 0,
+            moves: 0,
+            rng: rng,
+        };
+        state.generate_tile();
+        state.generate_tile();
+        state
+    }
+
+    // 保存した状態から復元する。盤面の大きさが合わない場合は None
+    pub fn from_parts(width: i32, height: i32, grid: Vec<i32>, score: i32, moves: i32, rng: GameRng) -> Option<GameState> {
+        if check_board_size(width, height).is_err() || grid.len() != (width * height) as usize {
+            return None;
+        }
+
+        Some(GameState {
+            width: width,
+            height: height,
+            grid: grid,
+            score: score,
+            moves: moves,
+            rng: rng,
+        })
+    }
+
+    // 行ごとのスコアの並びから、スコアと手数が0の盤面を作る。テストで盤面を書くため
+    // 大きさが不正な場合は panic する
+    pub fn from_rows(rows: &[&[i32]]) -> GameState {
+        let grid = rows.iter().flat_map(|row| row.iter().cloned()).collect();
+        GameState::from_parts(rows[0].len() as i32, rows.len() as i32, grid, 0, 0, GameRng::new(1)).unwrap()
+    }
+
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    pub fn score(&self) -> i32 {
+        self.score
+    }
+
+    pub fn moves(&self) -> i32 {
+        self.moves
+    }
+
+    pub fn grid(&self) -> &[i32] {
+        &self.grid
+    }
+
+    pub fn rng(&self) -> &GameRng {
+        &self.rng
+    }
+
+    // 座標のタイルのスコアを返す。空きマスは0
+    pub fn get(&self, x: i32, y: i32) -> i32 {
+        self.grid[self.index(x, y)]
+    }
+
+    // 盤面で最大のタイルのスコア
+    pub fn max_tile(&self) -> i32 {
+        self.grid.iter().cloned().max().unwrap_or(0)
+    }
+
+    // 空きマスがなく、隣り合う同じスコアのタイルもない場合、ゲームオーバー
+    pub fn is_game_over(&self) -> bool {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let score = self.get(x, y);
+                if score == 0 {
+                    return false;
+                }
+                if x + 1 < self.width && self.get(x + 1, y) == score {
+                    return false;
+                }
+                if y + 1 < self.height && self.get(x, y + 1) == score {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    // 空きマスの座標の一覧
+    pub fn empty_cells(&self) -> Vec<(i32, i32)> {
+        (0..self.grid.len())
+            .filter(|&i| self.grid[i] == 0)
+            .map(|i| (i as i32 % self.width, i as i32 / self.width))
+            .collect()
+    }
+
+    // 乱数を使わずにタイルを置く。AIの探索や盤面の準備に使う
+    pub fn place_tile(&mut self, x: i32, y: i32, score: i32) {
+        self.set(x, y, score);
+    }
+
+    fn set(&mut self, x: i32, y: i32, score: i32) {
+        let i = self.index(x, y);
+        self.grid[i] = score;
+    }
+
+    fn index(&self, x: i32, y: i32) -> usize {
+        (y * self.width + x) as usize
+    }
+
+    pub fn generate_tile(&mut self) -> Option<(i32, i32, i32)> {
+        // 空きマスの一覧
+        let empty: Vec<usize> = (0..self.grid.len()).filter(|&i| self.grid[i] == 0).collect();
+
+        // 空きマスがない場合、タイル生成しない
+        if empty.is_empty() {
+            return None;
+        }
+
+        // 空きマスから一様にランダムで選ぶ。乱数の使い方を固定して、シードから再現できるようにする
+        let i = empty[self.rng.gen_range(0, empty.len())];
+        let score = if self.rng.gen_range(0, 10) == 0 {
+            4
+        } else {
+            2
+        };
+        self.grid[i] = score;
+        Some((i as i32 % self.width, i as i32 / self.width, score))
+    }
+
+    // タイルを動かして、盤面が変化した場合は新しいタイルを生成する
+    pub fn apply_move(&mut self, direction: Direction) -> MoveOutcome {
+        let mut outcome = self.slide(direction);
+        if outcome.moved {
+            self.moves += 1;
+            outcome.spawned = self.generate_tile();
+        }
+        outcome
+    }
+
+    // タイルを動かしてマージするだけで、新しいタイルは生成しない。AIの探索などで使う
+    pub fn slide(&mut self, direction: Direction) -> MoveOutcome {
+        let mut moves = Vec::<TileMove>::new();
+        let mut score_gained = 0;
+
+        for line in self.lines(direction) {
+            // 次にタイルを置く位置
+            let mut target = 0;
+            // 直前に置いたタイルがまだマージできるか
+            let mut can_merge = false;
+
+            for &(x, y) in line.iter() {
+                let score = self.get(x, y);
+                if score == 0 {
+                    continue;
+                }
+
+                // 直前に置いたタイルと同じスコアならマージする
+                if can_merge {
+                    let (dx, dy) = line[target - 1];
+                    if self.get(dx, dy) == score {
+                        self.set(x, y, 0);
+                        self.set(dx, dy, score * 2);
+                        score_gained += score * 2;
+                        moves.push(TileMove { from_x: x, from_y: y, to_x: dx, to_y: dy, merged: true });
+                        can_merge = false;
+                        continue;
+                    }
+                }
+
+                // 空いている位置まで詰める
+                let (dx, dy) = line[target];
+                if (dx, dy) != (x, y) {
+                    self.set(x, y, 0);
+                    self.set(dx, dy, score);
+                    moves.push(TileMove { from_x: x, from_y: y, to_x: dx, to_y: dy, merged: false });
+                }
+                target += 1;
+                can_merge = true;
+            }
+        }
+
+        self.add_score(score_gained);
+
+        MoveOutcome {
+            moved: !moves.is_empty(),
+            moves: moves,
+            score_gained: score_gained,
+            spawned: None,
+        }
+    }
+
+    // 動かす向きの端から順に並べた座標の列
+    fn lines(&self, direction: Direction) -> Vec<Vec<(i32, i32)>> {
+        let (w, h) = (self.width, self.height);
+        match direction {
+            Direction::Up => (0..w).map(|x| (0..h).map(|y| (x, y)).collect()).collect(),
+            Direction::Down => (0..w).map(|x| (0..h).rev().map(|y| (x, y)).collect()).collect(),
+            Direction::Left => (0..h).map(|y| (0..w).map(|x| (x, y)).collect()).collect(),
+            Direction::Right => (0..h).map(|y| (0..w).rev().map(|x| (x, y)).collect()).collect(),
+        }
+    }
+
+    fn add_score(&mut self, score: i32) {
+        self.score += score;
+    }
+}
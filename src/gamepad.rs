@@ -0,0 +1,357 @@
+use std::collections::VecDeque;
+
+use crate::{game::Direction, key_bindings::Action, settings::Settings};
+
+// ボタンの番号。SDL のゲームコントローラーと同じ並びで、settings.json でもこの番号を使う
+//   0: A (下)  1: B (右)  2: X (左)  3: Y (上)  4: Back  5: Guide  6: Start
+//   7: 左スティック押し込み  8: 右スティック押し込み  9: L  10: R  11 - 14: D-pad の上下左右
+static DPAD_UP: u8 = 11;
+static DPAD_DOWN: u8 = 12;
+static DPAD_LEFT: u8 = 13;
+static DPAD_RIGHT: u8 = 14;
+
+// 操作を割り当てられる D-pad 以外のボタンの番号か
+pub fn is_action_button(button: i32) -> bool {
+    (0..DPAD_UP as i32).contains(&button)
+}
+
+// 左スティックの軸の番号。y は下向きが正
+static STICK_X: u8 = 0;
+static STICK_Y: u8 = 1;
+
+// コントローラーからの入力。Controllers やテスト用の MockController から作る
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ControllerEvent {
+    Press(u8),
+    Release(u8),
+    // 軸の番号と位置 (-1.0 - 1.0)
+    Axis(u8, f64),
+}
+
+// 接続しているコントローラー。Piston のウィンドウはコントローラーのイベントを出さないため、gilrs から読む
+// gamepad フィーチャーなしでビルドした場合 (初期設定) や、使えない環境では何も返さない
+pub struct Controllers {
+    #[cfg(feature = "gamepad")]
+    gilrs: Option<gilrs::Gilrs>,
+}
+
+impl Controllers {
+    #[cfg(feature = "gamepad")]
+    pub fn new() -> Controllers {
+        let gilrs = match gilrs::Gilrs::new() {
+            Ok(gilrs) => Some(gilrs),
+            Err(e) => {
+                println!("WARNING: Gamepads are not available: {}", e);
+                None
+            },
+        };
+        Controllers {
+            gilrs: gilrs,
+        }
+    }
+
+    #[cfg(not(feature = "gamepad"))]
+    pub fn new() -> Controllers {
+        Controllers {}
+    }
+
+    // 前回から届いたイベント
+    #[cfg(feature = "gamepad")]
+    pub fn poll(&mut self) -> Vec<ControllerEvent> {
+        let mut events = Vec::new();
+        if let Some(ref mut gilrs) = self.gilrs {
+            while let Some(event) = gilrs.next_event() {
+                events.extend(from_gilrs(event.event));
+            }
+        }
+        events
+    }
+
+    #[cfg(not(feature = "gamepad"))]
+    pub fn poll(&mut self) -> Vec<ControllerEvent> {
+        Vec::new()
+    }
+}
+
+impl Default for Controllers {
+    fn default() -> Controllers {
+        Controllers::new()
+    }
+}
+
+#[cfg(feature = "gamepad")]
+fn from_gilrs(event: gilrs::EventType) -> Option<ControllerEvent> {
+    use gilrs::{Axis, EventType};
+
+    match event {
+        EventType::ButtonPressed(button, _) => button_number(button).map(ControllerEvent::Press),
+        EventType::ButtonReleased(button, _) => button_number(button).map(ControllerEvent::Release),
+        EventType::AxisChanged(Axis::LeftStickX, position, _) => Some(ControllerEvent::Axis(STICK_X, position as f64)),
+        // gilrs は上向きが正
+        EventType::AxisChanged(Axis::LeftStickY, position, _) => Some(ControllerEvent::Axis(STICK_Y, -position as f64)),
+        _ => None,
+    }
+}
+
+// gilrs のボタンを上の番号に変換する。位置で対応させるので、South が A、East が B になる
+#[cfg(feature = "gamepad")]
+fn button_number(button: gilrs::Button) -> Option<u8> {
+    use gilrs::Button;
+
+    match button {
+        Button::South => Some(0),
+        Button::East => Some(1),
+        Button::West => Some(2),
+        Button::North => Some(3),
+        Button::Select => Some(4),
+        Button::Mode => Some(5),
+        Button::Start => Some(6),
+        Button::LeftThumb => Some(7),
+        Button::RightThumb => Some(8),
+        Button::LeftTrigger => Some(9),
+        Button::RightTrigger => Some(10),
+        Button::DPadUp => Some(DPAD_UP),
+        Button::DPadDown => Some(DPAD_DOWN),
+        Button::DPadLeft => Some(DPAD_LEFT),
+        Button::DPadRight => Some(DPAD_RIGHT),
+        _ => None,
+    }
+}
+
+// コントローラーの入力を操作に変換する
+//
+// D-pad か左スティックで向きを押している間、
+// 押した時に1回動かし、repeat_delay 秒ごとに繰り返す。スティックは dead_zone より小さい傾きを無視する
+pub struct Gamepad {
+    dead_zone: f64,
+    repeat_delay: f64,
+    undo_button: i32,
+    restart_button: i32,
+
+    // 押している D-pad の向きを押した順に並べたもの。最後に押した向きに動かす
+    dpad: Vec<Direction>,
+    stick: [f64; 2],
+    // 押している向きと、押してからの経過時間
+    held: Option<Direction>,
+    held_elapsed: f64,
+    actions: Vec<Action>,
+}
+
+impl Gamepad {
+    pub fn new(settings: &Settings) -> Gamepad {
+        Gamepad {
+            dead_zone: settings.gamepad_dead_zone,
+            repeat_delay: settings.gamepad_repeat_delay,
+            undo_button: settings.gamepad_undo_button,
+            restart_button: settings.gamepad_restart_button,
+
+            dpad: Vec::new(),
+            stick: [0.0, 0.0],
+            held: None,
+            held_elapsed: 0.0,
+            actions: Vec::new(),
+        }
+    }
+
+    pub fn handle(&mut self, event: ControllerEvent) {
+        match event {
+            ControllerEvent::Press(button) => {
+                if let Some(direction) = dpad_direction(button) {
+                    self.dpad.retain(|&d| d != direction);
+                    self.dpad.push(direction);
+                } else if button as i32 == self.undo_button {
+                    self.actions.push(Action::Undo);
+                } else if button as i32 == self.restart_button {
+                    self.actions.push(Action::Restart);
+                }
+            },
+            ControllerEvent::Release(button) => {
+                // 離した向きだけ外して、まだ押している向きがあればそちらに戻る
+                if let Some(direction) = dpad_direction(button) {
+                    self.dpad.retain(|&d| d != direction);
+                }
+            },
+            ControllerEvent::Axis(axis, position) if axis == STICK_X => self.stick[0] = position,
+            ControllerEvent::Axis(axis, position) if axis == STICK_Y => self.stick[1] = position,
+            ControllerEvent::Axis(_, _) => {},
+        }
+        self.update_held();
+    }
+
+    // 時間を進めて、その間の操作を返す
+    pub fn update(&mut self, dt: f64) -> Vec<Action> {
+        if let Some(direction) = self.held {
+            self.held_elapsed += dt;
+            if self.repeat_delay > 0.0 && self.held_elapsed >= self.repeat_delay {
+                self.held_elapsed -= self.repeat_delay;
+                self.actions.push(Action::from_direction(direction));
+            }
+        }
+        self.actions.drain(..).collect()
+    }
+
+    fn update_held(&mut self) {
+        let direction = self.dpad.last().cloned().or_else(|| self.stick_direction());
+        if direction != self.held {
+            self.held = direction;
+            self.held_elapsed = 0.0;
+            if let Some(direction) = direction {
+                self.actions.push(Action::from_direction(direction));
+            }
+        }
+    }
+
+    // スティックを大きく傾けている軸の向き。y は下向きが正
+    fn stick_direction(&self) -> Option<Direction> {
+        let [x, y] = self.stick;
+        if x.abs().max(y.abs()) < self.dead_zone {
+            None
+        } else if x.abs() > y.abs() {
+            Some(if x > 0.0 { Direction::Right } else { Direction::Left })
+        } else {
+            Some(if y > 0.0 { Direction::Down } else { Direction::Up })
+        }
+    }
+}
+
+fn dpad_direction(button: u8) -> Option<Direction> {
+    match button {
+        b if b == DPAD_UP => Some(Direction::Up),
+        b if b == DPAD_DOWN => Some(Direction::Down),
+        b if b == DPAD_LEFT => Some(Direction::Left),
+        b if b == DPAD_RIGHT => Some(Direction::Right),
+        _ => None,
+    }
+}
+
+// 決めた時刻にイベントを出す仮想のコントローラー。実機なしで入力を試すのに使う
+pub struct MockController {
+    // (時刻, イベント) を時刻順に並べたもの
+    events: VecDeque<(f64, ControllerEvent)>,
+    time: f64,
+}
+
+impl MockController {
+    pub fn new(mut events: Vec<(f64, ControllerEvent)>) -> MockController {
+        events.sort_by(|a, b| a.0.total_cmp(&b.0));
+        MockController {
+            events: events.into_iter().collect(),
+            time: 0.0,
+        }
+    }
+
+    // 時間を進めて、その時刻までのイベントを返す
+    pub fn poll(&mut self, dt: f64) -> Vec<ControllerEvent> {
+        self.time += dt;
+        let mut events = Vec::new();
+        while self.events.front().is_some_and(|&(time, _)| time <= self.time) {
+            events.push(self.events.pop_front().unwrap().1);
+        }
+        events
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.events.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 仮想のコントローラーを 0.05 秒ずつ進めて、出てきた操作を集める
+    fn run(events: Vec<(f64, ControllerEvent)>, duration: f64) -> Vec<Action> {
+        let settings = Settings::default_settings();
+        let mut gamepad = Gamepad::new(&settings);
+        let mut controller = MockController::new(events);
+        let mut actions = Vec::new();
+
+        let dt = 0.05;
+        let mut time = 0.0;
+        while time < duration {
+            for event in controller.poll(dt) {
+                gamepad.handle(event);
+            }
+            actions.extend(gamepad.update(dt));
+            time += dt;
+        }
+        assert!(controller.is_finished());
+        actions
+    }
+
+    #[test]
+    fn dpad_moves_once_per_press() {
+        let actions = run(vec![
+            (0.0, ControllerEvent::Press(DPAD_LEFT)),
+            (0.1, ControllerEvent::Release(DPAD_LEFT)),
+            (0.2, ControllerEvent::Press(DPAD_UP)),
+            (0.3, ControllerEvent::Release(DPAD_UP)),
+        ], 0.5);
+        assert_eq!(actions, vec![Action::Left, Action::Up]);
+    }
+
+    #[test]
+    fn releasing_one_dpad_button_keeps_the_other_held() {
+        let settings = Settings::default_settings();
+        let delay = settings.gamepad_repeat_delay;
+        let actions = run(vec![
+            (0.0, ControllerEvent::Press(DPAD_LEFT)),
+            (0.1, ControllerEvent::Press(DPAD_UP)),
+            (0.2, ControllerEvent::Release(DPAD_UP)),
+            (0.2 + delay * 1.5, ControllerEvent::Release(DPAD_LEFT)),
+        ], delay * 3.0);
+        // 上を離すと左に戻り、押し続けている左が繰り返される
+        assert_eq!(actions, vec![Action::Left, Action::Up, Action::Left, Action::Left]);
+    }
+
+    #[test]
+    fn held_direction_repeats_after_the_delay() {
+        let settings = Settings::default_settings();
+        let actions = run(vec![
+            (0.0, ControllerEvent::Press(DPAD_RIGHT)),
+            (settings.gamepad_repeat_delay * 2.5, ControllerEvent::Release(DPAD_RIGHT)),
+        ], settings.gamepad_repeat_delay * 4.0);
+        assert_eq!(actions, vec![Action::Right, Action::Right, Action::Right]);
+    }
+
+    #[test]
+    fn stick_ignores_the_dead_zone() {
+        let settings = Settings::default_settings();
+        let small = settings.gamepad_dead_zone * 0.5;
+        let actions = run(vec![
+            (0.0, ControllerEvent::Axis(STICK_X, small)),
+            (0.05, ControllerEvent::Axis(STICK_Y, -small)),
+            (0.1, ControllerEvent::Axis(STICK_X, 0.0)),
+            (0.1, ControllerEvent::Axis(STICK_Y, -0.9)),
+            (0.15, ControllerEvent::Axis(STICK_Y, 0.0)),
+        ], 0.2);
+        assert_eq!(actions, vec![Action::Up]);
+    }
+
+    #[cfg(feature = "gamepad")]
+    #[test]
+    fn numbers_gilrs_buttons() {
+        use gilrs::{Button, EventType};
+
+        assert_eq!(button_number(Button::DPadUp), Some(DPAD_UP));
+        assert_eq!(button_number(Button::DPadRight), Some(DPAD_RIGHT));
+        assert_eq!(button_number(Button::Unknown), None);
+        assert_eq!(from_gilrs(EventType::Connected), None);
+
+        // 初期設定のボタンは B と Y
+        let settings = Settings::default_settings();
+        assert_eq!(button_number(Button::East), Some(settings.gamepad_undo_button as u8));
+        assert_eq!(button_number(Button::North), Some(settings.gamepad_restart_button as u8));
+    }
+
+    #[test]
+    fn face_buttons_restart_and_undo() {
+        let settings = Settings::default_settings();
+        let actions = run(vec![
+            (0.0, ControllerEvent::Press(settings.gamepad_undo_button as u8)),
+            (0.05, ControllerEvent::Release(settings.gamepad_undo_button as u8)),
+            (0.1, ControllerEvent::Press(settings.gamepad_restart_button as u8)),
+        ], 0.2);
+        assert_eq!(actions, vec![Action::Undo, Action::Restart]);
+    }
+}
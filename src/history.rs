@@ -0,0 +1,130 @@
+use std::collections::VecDeque;
+
+// 元に戻す・やり直すための状態の履歴
+pub struct History<T> {
+    undo_stack: VecDeque<T>,
+    redo_stack: Vec<T>,
+    // 保存する状態の最大数
+    capacity: usize,
+    // 元に戻せる回数。負の値は無制限
+    undo_limit: i32,
+    // 元に戻した回数
+    undo_count: i32,
+}
+
+impl<T> History<T> {
+    pub fn new(capacity: usize, undo_limit: i32) -> History<T> {
+        History {
+            undo_stack: VecDeque::new(),
+            redo_stack: Vec::new(),
+            capacity: capacity,
+            undo_limit: undo_limit,
+            undo_count: 0,
+        }
+    }
+
+    // 途中から再開したゲームの履歴。元に戻した回数だけ引き継ぐ
+    pub fn with_undo_count(capacity: usize, undo_limit: i32, undo_count: i32) -> History<T> {
+        History {
+            undo_count: undo_count,
+            ..History::new(capacity, undo_limit)
+        }
+    }
+
+    // 変更前の状態を保存する。やり直しの履歴は消える
+    pub fn push(&mut self, state: T) {
+        self.redo_stack.clear();
+        self.push_undo(state);
+    }
+
+    // 一つ前の状態を返す。現在の状態はやり直し用に保存する
+    // 元に戻せる回数を使い切った場合は None
+    pub fn undo(&mut self, current: T) -> Option<T> {
+        if !self.can_undo() {
+            return None;
+        }
+        let state = self.undo_stack.pop_back()?;
+        self.redo_stack.push(current);
+        self.undo_count += 1;
+        Some(state)
+    }
+
+    // 元に戻した状態を返す。現在の状態は元に戻す用に保存する
+    pub fn redo(&mut self, current: T) -> Option<T> {
+        let state = self.redo_stack.pop()?;
+        self.push_undo(current);
+        Some(state)
+    }
+
+    fn push_undo(&mut self, state: T) {
+        if self.capacity == 0 {
+            return;
+        }
+        // 最大数を超えた場合、一番古い状態を捨てる
+        if self.undo_stack.len() == self.capacity {
+            self.undo_stack.pop_front();
+        }
+        self.undo_stack.push_back(state);
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty() && (self.undo_limit < 0 || self.undo_count < self.undo_limit)
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    pub fn undo_count(&self) -> i32 {
+        self.undo_count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drops_the_oldest_state_over_capacity() {
+        let mut history = History::new(2, -1);
+        history.push(1);
+        history.push(2);
+        history.push(3);
+
+        assert_eq!(history.undo(4), Some(3));
+        assert_eq!(history.undo(3), Some(2));
+        assert_eq!(history.undo(2), None);
+        assert!(!history.can_undo());
+    }
+
+    #[test]
+    fn new_state_clears_redo() {
+        let mut history = History::new(8, -1);
+        history.push(1);
+        history.push(2);
+        assert_eq!(history.undo(3), Some(2));
+        assert!(history.can_redo());
+        assert_eq!(history.redo(2), Some(3));
+
+        assert_eq!(history.undo(3), Some(2));
+        history.push(2);
+        assert!(!history.can_redo());
+        assert_eq!(history.redo(5), None);
+    }
+
+    #[test]
+    fn refuses_undo_after_the_limit() {
+        let mut history = History::new(8, 2);
+        for state in 1..5 {
+            history.push(state);
+        }
+        assert_eq!(history.undo(5), Some(4));
+        assert_eq!(history.undo(4), Some(3));
+        assert!(!history.can_undo());
+        assert_eq!(history.undo(3), None);
+
+        // やり直しても、元に戻した回数は戻らない
+        assert_eq!(history.redo(3), Some(4));
+        assert_eq!(history.undo(4), None);
+    }
+}
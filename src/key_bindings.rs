@@ -0,0 +1,250 @@
+use piston_window::Key;
+use crate::game::Direction;
+
+// キーに割り当てられる操作
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Up,
+    Down,
+    Left,
+    Right,
+    Restart,
+    Undo,
+    Redo,
+    Hint,
+    Pause,
+    Quit,
+    KeepGoing,
+    Autoplay,
+    Strategy,
+}
+
+impl Action {
+    pub const ALL: [Action; 13] = [
+        Action::Up, Action::Down, Action::Left, Action::Right,
+        Action::Restart, Action::Undo, Action::Redo, Action::Hint, Action::Pause, Action::Quit,
+        Action::KeepGoing, Action::Autoplay, Action::Strategy,
+    ];
+
+    // タイルを動かす向きの操作
+    pub fn from_direction(direction: Direction) -> Action {
+        match direction {
+            Direction::Up => Action::Up,
+            Direction::Down => Action::Down,
+            Direction::Left => Action::Left,
+            Direction::Right => Action::Right,
+        }
+    }
+
+    // settings.json の key_bindings での名前
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Up => "up",
+            Action::Down => "down",
+            Action::Left => "left",
+            Action::Right => "right",
+            Action::Restart => "restart",
+            Action::Undo => "undo",
+            Action::Redo => "redo",
+            Action::Hint => "hint",
+            Action::Pause => "pause",
+            Action::Quit => "quit",
+            Action::KeepGoing => "keep_going",
+            Action::Autoplay => "autoplay",
+            Action::Strategy => "strategy",
+        }
+    }
+}
+
+pub static PRESET_NAMES: [&str; 3] = ["arrows", "wasd", "hjkl"];
+
+// 操作ごとに割り当てたキー
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyBindings {
+    keys: Vec<(Action, Vec<Key>)>,
+}
+
+impl KeyBindings {
+    // 用意してある割り当て。矢印キーはどのプリセットでも使える
+    //   arrows: 矢印キー
+    //   wasd:   WASD と矢印キー。自動プレイは O、戦略の切り替えは I
+    //   hjkl:   vim の hjkl と矢印キー。ヒントは /
+    pub fn preset(name: &str) -> Option<KeyBindings> {
+        let mut keys = vec![
+            (Action::Up, vec![Key::Up]),
+            (Action::Down, vec![Key::Down]),
+            (Action::Left, vec![Key::Left]),
+            (Action::Right, vec![Key::Right]),
+            (Action::Restart, vec![Key::Space]),
+            (Action::Undo, vec![Key::U]),
+            (Action::Redo, vec![Key::R]),
+            (Action::Hint, vec![Key::H]),
+            (Action::Pause, vec![Key::P]),
+            (Action::Quit, vec![Key::Escape]),
+            (Action::KeepGoing, vec![Key::C]),
+            (Action::Autoplay, vec![Key::A]),
+            (Action::Strategy, vec![Key::S]),
+        ];
+
+        let mut set = |action: Action, action_keys: Vec<Key>| {
+            for entry in keys.iter_mut() {
+                if entry.0 == action {
+                    entry.1 = action_keys.clone();
+                }
+            }
+        };
+        match name {
+            "arrows" => {},
+            "wasd" => {
+                set(Action::Up, vec![Key::W, Key::Up]);
+                set(Action::Down, vec![Key::S, Key::Down]);
+                set(Action::Left, vec![Key::A, Key::Left]);
+                set(Action::Right, vec![Key::D, Key::Right]);
+                set(Action::Autoplay, vec![Key::O]);
+                set(Action::Strategy, vec![Key::I]);
+            },
+            "hjkl" => {
+                set(Action::Up, vec![Key::K, Key::Up]);
+                set(Action::Down, vec![Key::J, Key::Down]);
+                set(Action::Left, vec![Key::H, Key::Left]);
+                set(Action::Right, vec![Key::L, Key::Right]);
+                set(Action::Hint, vec![Key::Slash]);
+            },
+            _ => return None,
+        }
+
+        Some(KeyBindings {
+            keys: keys,
+        })
+    }
+
+    // プリセットの一部の操作を、キーの名前の一覧で置き換える。空の一覧はプリセットのまま
+    // 知らない名前や、同じキーを複数の操作に割り当てた場合はエラー
+    pub fn from_names(preset: &str, overrides: &[(Action, &[String])]) -> Result<KeyBindings, String> {
+        let mut bindings = KeyBindings::preset(preset)
+            .ok_or_else(|| format!("unknown preset '{}' (expected one of {})", preset, PRESET_NAMES.join(", ")))?;
+
+        for &(action, names) in overrides.iter() {
+            if names.is_empty() {
+                continue;
+            }
+            let keys = names.iter()
+                .map(|name| key_from_name(name).ok_or_else(|| format!("{}: unknown key '{}'", action.name(), name)))
+                .collect::<Result<Vec<Key>, String>>()?;
+            for entry in bindings.keys.iter_mut() {
+                if entry.0 == action {
+                    entry.1 = keys.clone();
+                }
+            }
+        }
+
+        bindings.check_conflicts()?;
+        Ok(bindings)
+    }
+
+    // キーに割り当てた操作
+    pub fn action(&self, key: Key) -> Option<Action> {
+        self.keys.iter()
+            .find(|(_, keys)| keys.contains(&key))
+            .map(|&(action, _)| action)
+    }
+
+    pub fn keys(&self, action: Action) -> &[Key] {
+        self.keys.iter()
+            .find(|(a, _)| *a == action)
+            .map(|(_, keys)| &keys[..])
+            .unwrap_or(&[])
+    }
+
+    // 操作に割り当てた最初のキーの名前 ("SPACE" など)。画面の案内に使う
+    pub fn key_name(&self, action: Action) -> String {
+        match self.keys(action).first() {
+            Some(key) => format!("{:?}", key).to_uppercase(),
+            None => "?".to_string(),
+        }
+    }
+
+    fn check_conflicts(&self) -> Result<(), String> {
+        let mut conflicts = Vec::new();
+        for (i, (action, keys)) in self.keys.iter().enumerate() {
+            for key in keys.iter() {
+                for (other, other_keys) in self.keys[i + 1..].iter() {
+                    if other_keys.contains(key) {
+                        conflicts.push(format!("{:?} is bound to both {} and {}", key, action.name(), other.name()));
+                    }
+                }
+            }
+        }
+
+        if conflicts.is_empty() {
+            Ok(())
+        } else {
+            Err(conflicts.join("; "))
+        }
+    }
+}
+
+// Piston のキーの名前 ("Up", "W", "Space", "D1" など。大文字小文字は区別しない) からキーを探す
+pub fn key_from_name(name: &str) -> Option<Key> {
+    // Key のコードは ASCII と 0x4000_0039 から 0x4000_011A の範囲にある
+    (0..0x80u32).chain(0x4000_0039..=0x4000_011Au32)
+        .map(Key::from)
+        .filter(|&key| key != Key::Unknown)
+        .find(|key| format!("{:?}", key).eq_ignore_ascii_case(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets_have_no_conflicts() {
+        for name in PRESET_NAMES.iter() {
+            let bindings = KeyBindings::from_names(name, &[]).unwrap();
+            for action in Action::ALL.iter() {
+                assert!(!bindings.keys(*action).is_empty(), "{}: {} has no key", name, action.name());
+            }
+        }
+        assert_eq!(KeyBindings::preset("wasd").unwrap().action(Key::W), Some(Action::Up));
+        assert_eq!(KeyBindings::preset("hjkl").unwrap().action(Key::H), Some(Action::Left));
+    }
+
+    #[test]
+    fn names_the_first_key_of_an_action() {
+        let bindings = KeyBindings::preset("wasd").unwrap();
+        assert_eq!(bindings.key_name(Action::Up), "W");
+        assert_eq!(bindings.key_name(Action::Restart), "SPACE");
+
+        let restart = vec!["Return".to_string()];
+        let bindings = KeyBindings::from_names("arrows", &[(Action::Restart, &restart)]).unwrap();
+        assert_eq!(bindings.key_name(Action::Restart), "RETURN");
+    }
+
+    #[test]
+    fn parses_key_names() {
+        assert_eq!(key_from_name("Up"), Some(Key::Up));
+        assert_eq!(key_from_name("space"), Some(Key::Space));
+        assert_eq!(key_from_name("w"), Some(Key::W));
+        assert_eq!(key_from_name("D1"), Some(Key::D1));
+        assert_eq!(key_from_name("NumPad8"), Some(Key::NumPad8));
+        assert_eq!(key_from_name("Unknown"), None);
+        assert_eq!(key_from_name("nope"), None);
+    }
+
+    #[test]
+    fn rejects_conflicts_and_unknown_names() {
+        let undo = vec!["Space".to_string()];
+        let error = KeyBindings::from_names("arrows", &[(Action::Undo, &undo)]).unwrap_err();
+        assert!(error.contains("Space is bound to both restart and undo"), "{}", error);
+
+        let up = vec!["Shift-W".to_string()];
+        assert!(KeyBindings::from_names("arrows", &[(Action::Up, &up)]).is_err());
+        assert!(KeyBindings::from_names("dvorak", &[]).is_err());
+
+        // 衝突するキーを外せば使える
+        let restart = vec!["Return".to_string()];
+        let bindings = KeyBindings::from_names("arrows", &[(Action::Undo, &undo), (Action::Restart, &restart)]).unwrap();
+        assert_eq!(bindings.action(Key::Space), Some(Action::Undo));
+        assert_eq!(bindings.action(Key::Return), Some(Action::Restart));
+    }
+}
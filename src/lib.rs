@@ -0,0 +1,26 @@
+// 既存コードのスタイル (フィールド名の明示、単一パターンの match) を許可する
+#![allow(
+    clippy::redundant_field_names,
+    clippy::single_match
+)]
+
+pub mod ai;
+pub mod app;
+pub mod bitboard;
+pub mod best_score;
+pub mod board;
+pub mod game;
+pub mod gamepad;
+pub mod history;
+pub mod key_bindings;
+pub mod number_renderer;
+pub mod playback;
+pub mod replay;
+pub mod rng;
+pub mod saved_game;
+pub mod settings;
+pub mod settings_watcher;
+pub mod strategy;
+pub mod swipe;
+pub mod text_renderer;
+pub mod tile;
@@ -1,28 +1,38 @@
+use std::env;
+use std::path::Path;
+use std::process;
+use std::rc::Rc;
+
 // ウィンドウの表示や操作ができる
 use piston_window::*;
+use rust_2048_clone::{app, replay::Replay, saved_game::SavedGame, settings};
 
-mod app;
-mod board;
-mod number_renderer;
-mod settings;
-mod tile;
-
+#[allow(clippy::while_let_on_iterator)]
 fn main() {
     // オブジェクトを描画できる
     use opengl_graphics::GlGraphics;
     // 設定をロード
-    let settings = settings::Settings::load();
+    let settings = Rc::new(settings::Settings::load());
     // ウィンドウサイズを設定
-    let (width, height) = (settings.window_size[0], settings.window_size[1]);
+    let mut window_size = settings.window_size;
+    let (width, height) = (window_size[0], window_size[1]);
     // ウィンドウを初期化
     let mut window: PistonWindow = 
         WindowSettings::new("Rust-2048" , [width, height])
-            .exit_on_esc(true)
+            // 終了のキーは key_bindings で設定する
+            .exit_on_esc(false)
             .build()
             .unwrap_or_else(|e| { panic!("Failed to build PistonWindow: {}", e) });
 
     // 使うオブジェクトを初期化
-    let mut app = app::App::new(&settings);
+    // --replay を指定した場合はリプレイを再生する
+    // そうでない場合、前回の途中のゲームがあれば、再開するか確認する
+    let replay = load_replay(&settings);
+    let saved_game = if replay.is_none() { SavedGame::load() } else { None };
+    let mut app = app::App::new(settings, parse_seed(), saved_game);
+    if let Some(replay) = replay {
+        app.start_playback(replay);
+    }
 
     // アセットを読み込む
     app.load();
@@ -39,12 +49,84 @@ fn main() {
         // 変数の状態を更新
         if let Some(ref args) = e.update_args() {
             app.update(args);
+
+            // 設定を読み込み直して配置が変わった場合、ウィンドウの大きさを合わせる
+            if app.window_size() != window_size {
+                window_size = app.window_size();
+                window.set_size(window_size);
+            }
         }
 
-        // キーボード入力受付
+        // キーボード、マウスのボタン入力受付
         if let Some(ref args) = e.press_args() {
             app.key_press(args);
+            if app.should_quit() {
+                window.set_should_close(true);
+            }
+        }
+
+        // マウスのドラッグ、タッチパッドのスワイプ
+        if let Some(position) = e.mouse_cursor_args() {
+            app.mouse_move(position);
+        }
+        if let Some(delta) = e.mouse_scroll_args() {
+            app.mouse_scroll(delta);
+        }
+        if let Some(ref args) = e.release_args() {
+            app.release(args);
         }
     }
 
+    // 終了時に途中のゲームを保存
+    app.save_game();
+}
+
+// コマンドライン引数の値を返す。"--name value" と "--name=value" の両方を受け付ける
+fn arg_value(name: &str) -> Option<String> {
+    let args: Vec<String> = env::args().collect();
+    let prefix = format!("{}=", name);
+    for (i, arg) in args.iter().enumerate() {
+        if arg == name {
+            return args.get(i + 1).cloned();
+        }
+        if let Some(value) = arg.strip_prefix(&prefix) {
+            return Some(value.to_string());
+        }
+    }
+    None
+}
+
+// --seed <N> でタイル生成のシードを固定する
+fn parse_seed() -> Option<u64> {
+    let value = arg_value("--seed")?;
+    match value.parse::<u64>() {
+        Ok(seed) => Some(seed),
+        Err(e) => {
+            eprintln!("Invalid --seed value '{}': {}", value, e);
+            process::exit(1);
+        }
+    }
+}
+
+// --replay <path> で指定したリプレイを読み込む
+fn load_replay(settings: &settings::Settings) -> Option<Replay> {
+    let path = arg_value("--replay")?;
+    let replay = Replay::load(Path::new(&path)).unwrap_or_else(|e| {
+        eprintln!("Failed to load replay '{}': {}", path, e);
+        process::exit(1);
+    });
+
+    // 盤面の大きさが違うと表示できない
+    if replay.width != settings.tile_width || replay.height != settings.tile_height {
+        eprintln!("Replay '{}' is for a {}x{} board, but settings use {}x{}.",
+            path, replay.width, replay.height, settings.tile_width, settings.tile_height);
+        process::exit(1);
+    }
+    if replay.fingerprint != settings.fingerprint() {
+        println!("WARNING: Replay '{}' was recorded with different settings.", path);
+    }
+    if let Err(e) = replay.verify() {
+        println!("WARNING: {}", e);
+    }
+    Some(replay)
 }
@@ -2,25 +2,44 @@ use std::path::Path;
 use piston_window::*;
 use opengl_graphics::GlGraphics;
 use opengl_graphics::Texture as GlTexture;
+use crate::text_renderer::TextRenderer;
 
 static DIGITS_WIDTH: f64 = 20.0;
 static DIGITS_HEIGHT: f64 = 26.0;
 
 pub struct NumberRenderer {
-    image: GlTexture,
+    // 読み込めなかった場合は、組み込みの文字で描画する
+    image: Option<GlTexture>,
+    fallback: TextRenderer,
 }
 
 impl NumberRenderer {
-    pub fn new() -> NumberRenderer {
+    // アセットフォルダの digits.png を読み込む
+    pub fn new(asset_root: &Path) -> NumberRenderer {
+        let path = asset_root.join("digits.png");
+        let image = match GlTexture::from_path(&path, &TextureSettings::new()) {
+            Ok(image) => Some(image),
+            Err(e) => {
+                println!("WARNING: Failed to load {}: {}. Drawing numbers with the built-in font.", path.display(), e);
+                None
+            },
+        };
         NumberRenderer {
-            image: GlTexture::from_path(
-                Path::new("bin/assets/digits.png"),
-                &TextureSettings::new(),
-            ).unwrap(),
+            image: image,
+            fallback: TextRenderer::new(),
         }
     }
 
+    // 描く位置と大きさ、色、描画先を個別に受け取る
+    #[allow(clippy::too_many_arguments)]
     pub fn render(&self, number: u32, center_x: f64, center_y: f64, max_width: f64, color: [f32; 3], c: &Context, gl: &mut GlGraphics) {
+        let image = match self.image {
+            Some(ref image) => image,
+            None => {
+                self.fallback.render(&number.to_string(), center_x, center_y, max_width, color, c, gl);
+                return;
+            },
+        };
         // タイルのスコアから数字のVecを生成
         let digits = number_to_digits(number);
         // タイルに書き込むスコアの合計幅
@@ -43,9 +62,9 @@ impl NumberRenderer {
         // 数字を書き込む
         for digit in digits.iter() {
             Image::new_color([color[0], color[1], color[2], 1.0])
-                .src_rect([(*digit * DIGITS_WIDTH as u32) as f64, 0 as f64, DIGITS_WIDTH as f64, DIGITS_HEIGHT as f64])
+                .src_rect([(*digit * DIGITS_WIDTH as u32) as f64, 0.0, DIGITS_WIDTH, DIGITS_HEIGHT])
                 .rect([x, y, width, height])
-                .draw(&self.image, &DrawState::default(), c.transform, gl);
+                .draw(image, &DrawState::default(), c.transform, gl);
 
             x += width;
         }
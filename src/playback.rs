@@ -0,0 +1,99 @@
+use crate::{game::{Direction, GameState}, replay::Replay};
+
+// 再生速度の段階
+static SPEEDS: [f64; 5] = [0.5, 1.0, 2.0, 4.0, 8.0];
+// 1倍速で1手を進める間隔 (秒)
+static STEP_INTERVAL: f64 = 0.4;
+
+// リプレイの再生状態
+pub struct Playback {
+    replay: Replay,
+    // 適用済みの手数
+    position: usize,
+    paused: bool,
+    speed_index: usize,
+    // 前の手を進めてからの経過時間
+    elapsed: f64,
+}
+
+impl Playback {
+    pub fn new(replay: Replay) -> Playback {
+        Playback {
+            replay: replay,
+            position: 0,
+            paused: false,
+            speed_index: 1,
+            elapsed: 0.0,
+        }
+    }
+
+    pub fn initial_state(&self) -> GameState {
+        self.replay.initial_state()
+    }
+
+    pub fn speed(&self) -> f64 {
+        SPEEDS[self.speed_index]
+    }
+
+    pub fn faster(&mut self) {
+        if self.speed_index + 1 < SPEEDS.len() {
+            self.speed_index += 1;
+        }
+    }
+
+    pub fn slower(&mut self) {
+        if self.speed_index > 0 {
+            self.speed_index -= 1;
+        }
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+        self.elapsed = 0.0;
+    }
+
+    // 時間を進めて、次の手を進める時にその向きを返す
+    pub fn tick(&mut self, dt: f64) -> Option<Direction> {
+        if self.paused || self.position >= self.replay.directions.len() {
+            return None;
+        }
+
+        self.elapsed += dt * self.speed();
+        if self.elapsed < STEP_INTERVAL {
+            return None;
+        }
+        self.elapsed = 0.0;
+        self.next()
+    }
+
+    // 一時停止して1手進める
+    pub fn step_forward(&mut self) -> Option<Direction> {
+        self.paused = true;
+        self.next()
+    }
+
+    // 一時停止して1手戻し、戻した後の盤面を返す
+    pub fn step_back(&mut self) -> Option<GameState> {
+        self.paused = true;
+        if self.position == 0 {
+            return None;
+        }
+        self.position -= 1;
+        Some(self.replay.state_at(self.position))
+    }
+
+    fn next(&mut self) -> Option<Direction> {
+        let direction = *self.replay.directions.get(self.position)?;
+        self.position += 1;
+        Some(direction)
+    }
+
+    // 画面に表示する再生状態
+    pub fn status_text(&self) -> String {
+        format!("{}/{} {}X{}",
+            self.position,
+            self.replay.directions.len(),
+            self.speed(),
+            if self.paused { " PAUSED" } else { "" })
+    }
+}
@@ -0,0 +1,273 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::{game::{check_board_size, Direction, GameState}, rng::GameRng, settings::{path_beside_settings, Settings}};
+
+// ファイルの先頭に書くマジックナンバーとフォーマットのバージョン
+static REPLAY_MAGIC: &str = "RUST2048-REPLAY";
+static REPLAY_VERSION: u32 = 1;
+static REPLAY_FOLDER: &str = "replays";
+
+// 1ゲーム分のリプレイ
+//
+// テキスト形式で、1行に1項目を書く:
+//   RUST2048-REPLAY 1
+//   seed 12345
+//   settings 5f1c9a0e2d3b4c6a
+//   size 4 4
+//   moves ULLRDU...
+//   score 1234
+//   grid 2,4,0,...
+// score と grid は最終状態で、読み込んだ時の検証に使う
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replay {
+    pub seed: u64,
+    pub fingerprint: u64,
+    pub width: i32,
+    pub height: i32,
+    pub directions: Vec<Direction>,
+    pub final_score: i32,
+    pub final_grid: Vec<i32>,
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(io::Error),
+    // 読み込めない行 (行番号, 内容)
+    Format(usize, String),
+    UnsupportedVersion(u32),
+    // 再現した最終状態が記録と違う
+    Mismatch(String),
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ReplayError::Io(ref e) => write!(f, "{}", e),
+            ReplayError::Format(line, ref message) => write!(f, "line {}: {}", line, message),
+            ReplayError::UnsupportedVersion(version) => write!(f, "unsupported replay version {}", version),
+            ReplayError::Mismatch(ref message) => write!(f, "replay does not reproduce the recorded game: {}", message),
+        }
+    }
+}
+
+impl From<io::Error> for ReplayError {
+    fn from(e: io::Error) -> ReplayError {
+        ReplayError::Io(e)
+    }
+}
+
+impl Replay {
+    // 最初からの操作の向きと最終状態から作る
+    pub fn new(settings: &Settings, game: &GameState, directions: &[Direction]) -> Replay {
+        Replay {
+            seed: game.rng().seed(),
+            fingerprint: settings.fingerprint(),
+            width: game.width(),
+            height: game.height(),
+            directions: directions.to_vec(),
+            final_score: game.score(),
+            final_grid: game.grid().to_vec(),
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        let directions: String = self.directions.iter().map(|d| d.to_char()).collect();
+        let grid: Vec<String> = self.final_grid.iter().map(|score| score.to_string()).collect();
+
+        format!("{} {}\nseed {}\nsettings {:016x}\nsize {} {}\nmoves {}\nscore {}\ngrid {}\n",
+            REPLAY_MAGIC, REPLAY_VERSION,
+            self.seed,
+            self.fingerprint,
+            self.width, self.height,
+            directions,
+            self.final_score,
+            grid.join(","))
+    }
+
+    pub fn parse(text: &str) -> Result<Replay, ReplayError> {
+        let mut lines = text.lines().enumerate().map(|(i, line)| (i + 1, line.trim()));
+
+        // ヘッダーとバージョンを確認
+        let (_, header) = lines.next().ok_or_else(|| ReplayError::Format(1, "empty file".to_string()))?;
+        let version = match header.split_whitespace().collect::<Vec<&str>>()[..] {
+            [magic, version] if magic == REPLAY_MAGIC => version.parse::<u32>()
+                .map_err(|_| ReplayError::Format(1, format!("invalid version '{}'", version)))?,
+            _ => return Err(ReplayError::Format(1, "not a replay file".to_string())),
+        };
+        if version != REPLAY_VERSION {
+            return Err(ReplayError::UnsupportedVersion(version));
+        }
+
+        let mut seed = None;
+        let mut fingerprint = None;
+        let mut size = None;
+        let mut directions = None;
+        let mut final_score = None;
+        let mut final_grid = None;
+
+        for (n, line) in lines {
+            if line.is_empty() {
+                continue;
+            }
+            let (key, value) = match line.find(' ') {
+                Some(i) => (&line[..i], line[i + 1..].trim()),
+                None => (line, ""),
+            };
+            let invalid = || ReplayError::Format(n, format!("invalid {} '{}'", key, value));
+
+            match key {
+                "seed" => seed = Some(value.parse::<u64>().map_err(|_| invalid())?),
+                "settings" => fingerprint = Some(u64::from_str_radix(value, 16).map_err(|_| invalid())?),
+                "size" => {
+                    let parts = value.split_whitespace()
+                        .map(|v| v.parse::<i32>())
+                        .collect::<Result<Vec<i32>, _>>()
+                        .map_err(|_| invalid())?;
+                    match parts[..] {
+                        [width, height] if check_board_size(width, height).is_ok() => size = Some((width, height)),
+                        _ => return Err(invalid()),
+                    }
+                },
+                "moves" => directions = Some(value.chars()
+                    .map(Direction::from_char)
+                    .collect::<Option<Vec<Direction>>>()
+                    .ok_or_else(invalid)?),
+                "score" => final_score = Some(value.parse::<i32>().map_err(|_| invalid())?),
+                "grid" => final_grid = Some(value.split(',')
+                    .map(|v| v.parse::<i32>())
+                    .collect::<Result<Vec<i32>, _>>()
+                    .map_err(|_| invalid())?),
+                _ => return Err(ReplayError::Format(n, format!("unknown key '{}'", key))),
+            }
+        }
+
+        let missing = |key: &str| ReplayError::Format(0, format!("missing '{}'", key));
+        let (width, height) = size.ok_or_else(|| missing("size"))?;
+        Ok(Replay {
+            seed: seed.ok_or_else(|| missing("seed"))?,
+            fingerprint: fingerprint.ok_or_else(|| missing("settings"))?,
+            width: width,
+            height: height,
+            directions: directions.ok_or_else(|| missing("moves"))?,
+            final_score: final_score.ok_or_else(|| missing("score"))?,
+            final_grid: final_grid.ok_or_else(|| missing("grid"))?,
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Replay, ReplayError> {
+        Replay::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut file = File::create(path)?;
+        file.write_all(self.to_text().as_bytes())
+    }
+
+    // 設定ファイルと同じフォルダの replays フォルダに保存して、保存先を返す
+    pub fn save_to_replay_folder(&self) -> io::Result<PathBuf> {
+        let folder = path_beside_settings(REPLAY_FOLDER)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "can't find exe path"))?;
+        fs::create_dir_all(&folder)?;
+
+        let time = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        let path = folder.join(format!("replay-{}-{}.txt", time, self.seed));
+        self.save(&path)?;
+        Ok(path)
+    }
+
+    // 最初の盤面
+    pub fn initial_state(&self) -> GameState {
+        GameState::new(self.width, self.height, GameRng::new(self.seed))
+    }
+
+    // 最初から count 手を適用した盤面
+    pub fn state_at(&self, count: usize) -> GameState {
+        let mut game = self.initial_state();
+        for direction in self.directions.iter().take(count) {
+            game.apply_move(*direction);
+        }
+        game
+    }
+
+    // 記録した操作を最初から適用して、最終状態を再現する
+    pub fn rebuild(&self) -> GameState {
+        self.state_at(self.directions.len())
+    }
+
+    // 再現した最終状態が記録と一致するか確かめる
+    pub fn verify(&self) -> Result<GameState, ReplayError> {
+        let game = self.rebuild();
+        if game.score() != self.final_score {
+            return Err(ReplayError::Mismatch(format!("score {} != {}", game.score(), self.final_score)));
+        }
+        if game.grid() != &self.final_grid[..] {
+            return Err(ReplayError::Mismatch("final grid differs".to_string()));
+        }
+        Ok(game)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recorded_replay() -> Replay {
+        let mut game = GameState::new(4, 4, GameRng::new(2048));
+        let mut directions = Vec::new();
+        for direction in [Direction::Left, Direction::Down, Direction::Right, Direction::Up].iter().cycle().take(40) {
+            if game.apply_move(*direction).moved {
+                directions.push(*direction);
+            }
+        }
+        Replay::new(&Settings::default_settings(), &game, &directions)
+    }
+
+    #[test]
+    fn round_trips_through_text() {
+        let replay = recorded_replay();
+        let parsed = Replay::parse(&replay.to_text()).unwrap();
+        assert_eq!(parsed, replay);
+        assert_eq!(parsed.verify().unwrap().grid(), &replay.final_grid[..]);
+    }
+
+    #[test]
+    fn rejects_bad_headers_and_versions() {
+        let text = recorded_replay().to_text();
+        let body = &text[text.find('\n').unwrap()..];
+
+        match Replay::parse(&format!("RUST2048-SAVE 1{}", body)) {
+            Err(ReplayError::Format(1, _)) => {},
+            other => panic!("expected a bad header, got {:?}", other),
+        }
+        match Replay::parse(&format!("{} {}{}", REPLAY_MAGIC, REPLAY_VERSION + 1, body)) {
+            Err(ReplayError::UnsupportedVersion(version)) => assert_eq!(version, REPLAY_VERSION + 1),
+            other => panic!("expected an unsupported version, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rejects_missing_settings() {
+        let text: String = recorded_replay().to_text().lines()
+            .filter(|line| !line.starts_with("settings"))
+            .map(|line| format!("{}\n", line))
+            .collect();
+        match Replay::parse(&text) {
+            Err(ReplayError::Format(_, message)) => assert!(message.contains("missing 'settings'"), "{}", message),
+            other => panic!("expected a missing settings line, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn verify_detects_a_different_final_state() {
+        let mut replay = recorded_replay();
+        replay.final_score += 4;
+        assert!(matches!(replay.verify(), Err(ReplayError::Mismatch(_))));
+
+        let mut replay = recorded_replay();
+        replay.directions.pop();
+        assert!(matches!(replay.verify(), Err(ReplayError::Mismatch(_))));
+    }
+}
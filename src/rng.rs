@@ -0,0 +1,56 @@
+use rand::{random, Rng, SeedableRng, XorShiftRng};
+
+// シードと生成回数から状態を復元できる乱数生成器
+#[derive(Debug, Clone)]
+pub struct GameRng {
+    seed: u64,
+    // これまでに生成した乱数の個数
+    draws: u64,
+    rng: XorShiftRng,
+}
+
+impl GameRng {
+    pub fn new(seed: u64) -> GameRng {
+        GameRng {
+            seed: seed,
+            draws: 0,
+            rng: XorShiftRng::from_seed(seed_to_words(seed)),
+        }
+    }
+
+    // シードをランダムに決める。入力しやすいように32ビットの範囲にする
+    pub fn from_entropy() -> GameRng {
+        GameRng::new(random::<u32>() as u64)
+    }
+
+    // 保存したシードと生成回数から復元する
+    pub fn restore(seed: u64, draws: u64) -> GameRng {
+        let mut rng = GameRng::new(seed);
+        for _ in 0..draws {
+            rng.next_u32();
+        }
+        rng
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn draws(&self) -> u64 {
+        self.draws
+    }
+}
+
+impl Rng for GameRng {
+    fn next_u32(&mut self) -> u32 {
+        self.draws += 1;
+        self.rng.next_u32()
+    }
+}
+
+// XorShiftRng のシードは全て0にできないため、定数と混ぜて4つの値にする
+fn seed_to_words(seed: u64) -> [u32; 4] {
+    let low = seed as u32;
+    let high = (seed >> 32) as u32;
+    [low, high, low ^ 0x9e37_79b9, high ^ 0x85eb_ca6b]
+}
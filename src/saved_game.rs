@@ -0,0 +1,213 @@
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Write};
+use serde::{Deserialize, Serialize};
+use crate::{board::Board, game::{Direction, GameState}, rng::GameRng, settings::{path_beside_settings, Settings}};
+
+static SAVE_FILENAME: &str = "save.json";
+
+// 途中まで進めたゲームの保存データ
+#[derive(Serialize, Deserialize)]
+pub struct SavedGame {
+    width: i32,
+    height: i32,
+    grid: Vec<i32>,
+    score: i32,
+    moves: i32,
+    seed: u64,
+    // 乱数を復元するため、生成した乱数の個数も保存する
+    rng_draws: u64,
+    // リプレイを作るため、最初からの操作の向きも保存する ("ULRD...")
+    // 向きを保存する前のファイルにはないため、空 (途中からのゲーム) として読み込む
+    #[serde(default)]
+    directions: String,
+    keep_going: bool,
+    // 元に戻した回数。再開した時に元に戻せる回数が戻らないようにする
+    // 保存していない古いファイルは、まだ元に戻していないものとして読み込む
+    #[serde(default)]
+    undo_count: i32,
+}
+
+impl SavedGame {
+    pub fn from_board(board: &Board) -> SavedGame {
+        let game = board.game();
+        SavedGame {
+            width: game.width(),
+            height: game.height(),
+            grid: game.grid().to_vec(),
+            score: game.score(),
+            moves: game.moves(),
+            seed: game.rng().seed(),
+            rng_draws: game.rng().draws(),
+            directions: board.directions()
+                .map(|directions| directions.iter().map(|d| d.to_char()).collect())
+                .unwrap_or_default(),
+            keep_going: board.keeps_going(),
+            undo_count: board.undo_count(),
+        }
+    }
+
+    // 保存した状態からゲームを復元する。盤面の大きさが合わない場合は None
+    pub fn to_game_state(&self) -> Option<GameState> {
+        GameState::from_parts(
+            self.width,
+            self.height,
+            self.grid.clone(),
+            self.score,
+            self.moves,
+            GameRng::restore(self.seed, self.rng_draws))
+    }
+
+    // 今の設定の盤面で再開できる場合。大きさが違うとボードの外にタイルを描いてしまう
+    pub fn fits(&self, settings: &Settings) -> bool {
+        self.width == settings.tile_width && self.height == settings.tile_height && self.to_game_state().is_some()
+    }
+
+    // 読み込めない向きがある場合は、途中からのゲームとして扱う
+    pub fn directions(&self) -> Vec<Direction> {
+        self.directions.chars()
+            .map(Direction::from_char)
+            .collect::<Option<Vec<Direction>>>()
+            .unwrap_or_default()
+    }
+
+    pub fn score(&self) -> i32 {
+        self.score
+    }
+
+    pub fn keep_going(&self) -> bool {
+        self.keep_going
+    }
+
+    pub fn undo_count(&self) -> i32 {
+        self.undo_count
+    }
+
+    // 保存ファイルがない場合、読み込めない場合は None
+    pub fn load() -> Option<SavedGame> {
+        let path = path_beside_settings(SAVE_FILENAME)?;
+        let file = File::open(&path).ok()?;
+        let reader = BufReader::new(file);
+
+        match serde_json::from_reader(reader) {
+            Ok(saved) => Some(saved),
+            Err(e) => {
+                println!("WARNING: Failed to load saved game: {}", e);
+                None
+            }
+        }
+    }
+
+    pub fn save(&self) {
+        let path = match path_beside_settings(SAVE_FILENAME) {
+            Some(path) => path,
+            None => {
+                println!("WARNING: Failed to save game: can't find exe path.");
+                return;
+            }
+        };
+
+        let result = serde_json::to_string(self)
+            .map_err(|e| e.to_string())
+            .and_then(|encoded| {
+                let mut writer = BufWriter::new(File::create(&path).map_err(|e| e.to_string())?);
+                writer.write_all(encoded.as_bytes()).map_err(|e| e.to_string())
+            });
+        if let Err(e) = result {
+            println!("WARNING: Failed to save game: {}", e);
+        }
+    }
+
+    // 終わったゲームは再開しないため、保存ファイルを消す
+    pub fn remove() {
+        if let Some(path) = path_beside_settings(SAVE_FILENAME) {
+            if path.exists() {
+                if let Err(e) = fs::remove_file(&path) {
+                    println!("WARNING: Failed to remove saved game: {}", e);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+
+    fn played_board(settings: &Rc<Settings>) -> Board {
+        let mut board = Board::new(settings.clone(), GameRng::new(42));
+        for direction in [Direction::Left, Direction::Up, Direction::Right, Direction::Down, Direction::Left].iter() {
+            board.apply_move(*direction);
+            board.finish_animations();
+        }
+        board
+    }
+
+    #[test]
+    fn round_trips_through_json() {
+        let settings = Rc::new(Settings::default_settings());
+        let board = played_board(&settings);
+        let saved = SavedGame::from_board(&board);
+
+        let loaded: SavedGame = serde_json::from_str(&serde_json::to_string(&saved).unwrap()).unwrap();
+        assert!(loaded.fits(&settings));
+        assert_eq!(loaded.score(), board.game().score());
+        assert_eq!(loaded.keep_going(), board.keeps_going());
+        assert_eq!(Some(&loaded.directions()[..]), board.directions());
+
+        // 乱数も復元するため、同じ操作で同じタイルが生成される
+        let mut restored = loaded.to_game_state().unwrap();
+        let mut original = board.game().clone();
+        assert_eq!(restored.grid(), original.grid());
+        assert_eq!(restored.moves(), original.moves());
+        for direction in [Direction::Up, Direction::Left, Direction::Down].iter() {
+            assert_eq!(restored.apply_move(*direction).spawned, original.apply_move(*direction).spawned);
+        }
+    }
+
+    #[test]
+    fn reads_saves_without_directions() {
+        let text = r#"{"width":4,"height":4,"grid":[2,0,0,0,0,0,0,0,0,0,0,0,0,0,2,0],"score":0,"moves":3,"seed":1,"rng_draws":6,"keep_going":false}"#;
+        let loaded: SavedGame = serde_json::from_str(text).unwrap();
+        assert!(loaded.directions().is_empty());
+        assert_eq!(loaded.undo_count(), 0);
+        assert!(loaded.to_game_state().is_some());
+    }
+
+    #[test]
+    fn keeps_the_undo_limit_used_up() {
+        let mut limited = Settings::default_settings();
+        limited.undo_limit = 1;
+        let settings = Rc::new(limited);
+        let mut board = played_board(&settings);
+        board.undo();
+        board.finish_animations();
+        assert!(!board.can_undo());
+
+        let text = serde_json::to_string(&SavedGame::from_board(&board)).unwrap();
+        let loaded: SavedGame = serde_json::from_str(&text).unwrap();
+        assert_eq!(loaded.undo_count(), 1);
+
+        // 再開して1手進めても、元に戻せる回数は使い切ったまま
+        let directions = loaded.directions();
+        let mut resumed = Board::from_game(settings, loaded.to_game_state().unwrap(), directions, loaded.keep_going(), loaded.undo_count());
+        for direction in Direction::ALL.iter() {
+            if resumed.apply_move(*direction).is_some_and(|outcome| outcome.moved) {
+                break;
+            }
+        }
+        resumed.finish_animations();
+        assert!(!resumed.can_undo());
+    }
+
+    #[test]
+    fn does_not_fit_other_board_sizes() {
+        let settings = Rc::new(Settings::default_settings());
+        let saved = SavedGame::from_board(&played_board(&settings));
+
+        let mut larger = Settings::default_settings();
+        larger.tile_width = 5;
+        larger.tile_height = 5;
+        assert!(!saved.fits(&larger));
+    }
+}
@@ -1,11 +1,136 @@
 
 use std::env::current_exe;
-use std::io::{BufWriter, BufReader, Write};
-use std::fs::{File};
-use std::path::Path;
-use rustc_serialize::{ json, Encodable, Decodable };
+use std::fmt;
+use std::io::{self, BufWriter, Write};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::{game::{check_board_size, largest_possible_tile}, gamepad::is_action_button, key_bindings::{Action, KeyBindings}};
 
-static SETTING_FILENAME: &'static str = "settings.json";
+// 設定ファイルの名前。拡張子は形式で決まる (settings.json, settings.toml, settings.ron)
+static SETTING_FILE_STEM: &str = "settings";
+
+// 設定ファイルの形式のバージョン。形式を変えたら上げて、MIGRATIONS に古い形式からの変換を足す
+//   1: version がない最初の形式
+//   2: tile_unknow_color を tile_unknown_color に改名
+pub static SETTINGS_VERSION: u64 = 2;
+
+// MIGRATIONS[i] はバージョン i + 1 の形式をバージョン i + 2 の形式に変換する
+static MIGRATIONS: [fn(&mut serde_json::Map<String, Value>); 1] = [
+    migrate_v1_to_v2,
+];
+
+fn migrate_v1_to_v2(fields: &mut serde_json::Map<String, Value>) {
+    if let Some(color) = fields.remove("tile_unknow_color") {
+        fields.entry("tile_unknown_color").or_insert(color);
+    }
+}
+
+// 設定ファイルと同じフォルダ (実行ファイルのフォルダ) にあるファイルのパス
+pub fn path_beside_settings(filename: &str) -> Option<PathBuf> {
+    let mut exe_path = current_exe().ok()?;
+    exe_path.pop();
+    Some(exe_path.join(Path::new(filename)))
+}
+
+// 設定ファイルの形式。ファイルの拡張子で決まる
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SettingsFormat {
+    #[default]
+    Json,
+    // TOML と RON はコメントを書ける
+    Toml,
+    Ron,
+}
+
+impl SettingsFormat {
+    // 同じフォルダに複数の設定ファイルがある場合は、この順に優先する
+    pub const ALL: [SettingsFormat; 3] = [SettingsFormat::Json, SettingsFormat::Toml, SettingsFormat::Ron];
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            SettingsFormat::Json => "json",
+            SettingsFormat::Toml => "toml",
+            SettingsFormat::Ron => "ron",
+        }
+    }
+
+    pub fn file_name(self) -> String {
+        format!("{}.{}", SETTING_FILE_STEM, self.extension())
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            SettingsFormat::Json => "JSON",
+            SettingsFormat::Toml => "TOML",
+            SettingsFormat::Ron => "RON",
+        }
+    }
+
+    // どの形式も、移行や知らない項目の確認のため一度 JSON の値にする
+    fn parse(self, text: &str) -> Result<Value, String> {
+        match self {
+            SettingsFormat::Json => serde_json::from_str(text).map_err(|e| e.to_string()),
+            SettingsFormat::Toml => toml::from_str(text).map_err(|e| e.to_string()),
+            SettingsFormat::Ron => ron::from_str(text).map_err(|e| e.to_string()),
+        }
+    }
+
+    fn encode<T: Serialize>(self, value: &T) -> Result<String, String> {
+        match self {
+            SettingsFormat::Json => serde_json::to_string_pretty(value).map_err(|e| e.to_string()),
+            SettingsFormat::Toml => toml::to_string_pretty(value).map_err(|e| e.to_string()),
+            SettingsFormat::Ron => ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default()).map_err(|e| e.to_string()),
+        }
+    }
+}
+
+// 実行ファイルのフォルダにある設定ファイルのうち、使うもの。複数ある場合は SettingsFormat::ALL の順に優先する
+pub fn find_settings_file() -> Option<(PathBuf, SettingsFormat)> {
+    SettingsFormat::ALL.iter()
+        .filter_map(|&format| path_beside_settings(&format.file_name()).map(|path| (path, format)))
+        .find(|(path, _)| path.exists())
+}
+
+// 設定ファイルを読み込めない理由。どれも読み込んだファイルの形式を持つ
+#[derive(Debug)]
+pub enum SettingsError {
+    Io(SettingsFormat, io::Error),
+    // 形式の文法として読めない
+    Parse(SettingsFormat, String),
+    // 型が違う項目 (項目のパス, 内容) の一覧
+    Decode(SettingsFormat, Vec<(String, String)>),
+    // このプログラムより新しい形式のファイル
+    UnsupportedVersion(SettingsFormat, u64),
+    // 値が正しくない項目 (項目のパス, 内容) の一覧
+    Invalid(SettingsFormat, Vec<(String, String)>),
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SettingsError::Io(format, ref e) => write!(f, "can't read {}: {}", format.file_name(), e),
+            SettingsError::Parse(format, ref message) => write!(f, "{} is not valid {}: {}", format.file_name(), format.name(), message),
+            SettingsError::Decode(format, ref problems) => {
+                write!(f, "{} has {} mistyped field(s):", format.file_name(), problems.len())?;
+                for (path, message) in problems.iter() {
+                    write!(f, "\n  {}: {}", path, message)?;
+                }
+                Ok(())
+            },
+            SettingsError::UnsupportedVersion(format, version) => write!(f, "{} is version {}, but this program only supports up to version {}",
+                format.file_name(), version, SETTINGS_VERSION),
+            SettingsError::Invalid(format, ref problems) => {
+                write!(f, "{} has {} invalid value(s):", format.file_name(), problems.len())?;
+                for (path, message) in problems.iter() {
+                    write!(f, "\n  {}: {}", path, message)?;
+                }
+                Ok(())
+            },
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct Settings {
@@ -23,10 +148,20 @@ pub struct Settings {
     pub tile_padding: f64,
     pub tile_background_color: [f32; 3],
     pub tiles_colors: Vec<[f32; 3]>,
-    pub tile_unknow_color: [f32; 3],
+    pub tile_unknown_color: [f32; 3],
     pub tile_move_time: f64,
     pub tile_new_time: f64,
     pub tile_combine_time: f64,
+    pub target_tile: i32,
+    pub undo_limit: i32,
+    pub input_queue_size: i32,
+    pub snap_animations: bool,
+    pub key_bindings: KeyBindings,
+    pub swipe_min_distance: f64,
+    pub gamepad_dead_zone: f64,
+    pub gamepad_repeat_delay: f64,
+    pub gamepad_undo_button: i32,
+    pub gamepad_restart_button: i32,
     pub best_rect: [f64; 4],
     pub score_rect: [f64; 4],
     pub label_color: [f32; 3],
@@ -36,16 +171,98 @@ pub struct Settings {
 }
 
 impl Settings {
+    // 設定ファイルを読み込む。読めない場合は問題を全て表示して、デフォルトの設定を使う
     pub fn load() -> Settings {
-        Settings::from_settings_in_json(&SettingsInJson::load())
+        Settings::try_load().unwrap_or_else(|e| {
+            println!("WARNING: {}", e);
+            println!("WARNING: Using the default settings.");
+            Settings::default_settings()
+        })
+    }
+
+    pub fn try_load() -> Result<Settings, SettingsError> {
+        let s = SettingsInJson::load()?;
+        s.validate()?;
+        let settings = Settings::from_settings_in_json(&s);
+        settings.check_asset_folder();
+        Ok(settings)
     }
 
-    fn from_settings_in_json<'a>(s: &'a SettingsInJson) -> Settings {
+    // アセットフォルダは起動したフォルダからの相対パスで探す。見つからなくても画像なしで動くため、警告だけ表示する
+    fn check_asset_folder(&self) {
+        if !Path::new(&self.asset_folder).is_dir() {
+            println!("WARNING: The asset folder '{}' can't be found. Images in it won't be drawn.", self.asset_folder);
+        }
+    }
+
+    // ファイルを使わないデフォルトの設定。テストやウィンドウなしの実行に使う
+    pub fn default_settings() -> Settings {
+        Settings::from_settings_in_json(&SettingsInJson::default_settings())
+    }
+
+    // タイルの色。2のi乗のタイルは tiles_colors[i]、色がない場合は tile_unknown_color
+    pub fn tile_color(&self, score: i32) -> [f32; 3] {
+        let i = (score as f64).log2() as usize;
+        if i > 0 && i < self.tiles_colors.len() {
+            self.tiles_colors[i]
+        } else {
+            self.tile_unknown_color
+        }
+    }
+
+    // タイルの数字の色
+    pub fn tile_text_color(&self, score: i32) -> [f32; 3] {
+        if score >= 8 {
+            self.text_light_color
+        } else {
+            self.text_dark_color
+        }
+    }
+
+    // ゲームの進み方に影響する設定のハッシュ (FNV-1a)。リプレイが同じルールか確かめるのに使う
+    pub fn fingerprint(&self) -> u64 {
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        for value in [self.tile_width, self.tile_height, self.target_tile].iter() {
+            for byte in value.to_le_bytes().iter() {
+                hash ^= *byte as u64;
+                hash = hash.wrapping_mul(0x0100_0000_01b3);
+            }
+        }
+        hash
+    }
+
+    // 途中のゲームに読み込み直した設定を使えるか。ゲームの進み方に影響する設定 (fingerprint) は変えられない
+    pub fn check_reload(&self, new: &Settings) -> Result<(), String> {
+        if new.fingerprint() == self.fingerprint() {
+            return Ok(());
+        }
+        let mut changes = Vec::new();
+        if (new.tile_width, new.tile_height) != (self.tile_width, self.tile_height) {
+            changes.push(format!("board size {}x{} to {}x{}", self.tile_width, self.tile_height, new.tile_width, new.tile_height));
+        }
+        if new.target_tile != self.target_tile {
+            changes.push(format!("target_tile {} to {}", self.target_tile, new.target_tile));
+        }
+        Err(format!("These settings can't change during a game: {}", changes.join(", ")))
+    }
+
+    fn from_settings_in_json(s: &SettingsInJson) -> Settings {
         let board_size = [
             s.tile_size * s.tile_width as f64 + s.tile_padding * (s.tile_width + 1) as f64,
             s.tile_size * s.tile_height as f64 + s.tile_padding * (s.tile_height + 1) as f64,
         ];
 
+        // 横に狭い盤面でもスコアボードが収まる幅にする
+        let window_width = [
+            s.board_padding * 2.0 + board_size[0],
+            s.score_rect[0] + s.score_rect[2] + s.board_padding,
+            s.best_rect[0] + s.best_rect[2] + s.board_padding,
+        ].iter().cloned().fold(0.0, f64::max);
+
+        // キーの割り当ては validate で確かめてある
+        let key_bindings = s.key_bindings.to_key_bindings()
+            .unwrap_or_else(|_| KeyBindings::preset("arrows").unwrap());
+
         let mut tiles_colors = Vec::<[f32; 3]>::new();
 
         for color in s.tiles_colors.iter() {
@@ -61,7 +278,7 @@ impl Settings {
             comment1_offset_y: s.comment1_offset_y,
             comment2_offset_y: s.comment2_offset_y,
             window_size: [
-                (s.board_padding * 2.0 + board_size[0]) as u32,
+                window_width as u32,
                 (s.board_padding * 2.0 + board_size[1] + s.board_offset_y) as u32,
             ],
             window_background_color: [
@@ -82,14 +299,24 @@ impl Settings {
                 s.tile_background_color[2] / 255.0,
             ],
             tiles_colors: tiles_colors,
-            tile_unknow_color: [
-                s.tile_unknow_color[0] / 255.0,
-                s.tile_unknow_color[1] / 255.0,
-                s.tile_unknow_color[2] / 255.0,
+            tile_unknown_color: [
+                s.tile_unknown_color[0] / 255.0,
+                s.tile_unknown_color[1] / 255.0,
+                s.tile_unknown_color[2] / 255.0,
             ],
             tile_move_time: s.tile_move_time,
             tile_new_time: s.tile_new_time,
             tile_combine_time: s.tile_combine_time,
+            target_tile: s.target_tile,
+            undo_limit: s.undo_limit,
+            input_queue_size: s.input_queue_size,
+            snap_animations: s.snap_animations,
+            key_bindings: key_bindings,
+            swipe_min_distance: s.swipe_min_distance,
+            gamepad_dead_zone: s.gamepad_dead_zone,
+            gamepad_repeat_delay: s.gamepad_repeat_delay,
+            gamepad_undo_button: s.gamepad_undo_button,
+            gamepad_restart_button: s.gamepad_restart_button,
             best_rect: [
                 s.best_rect[0],
                 s.best_rect[1],
@@ -126,8 +353,17 @@ impl Settings {
     }
 }
 
-#[derive(RustcEncodable, RustcDecodable)]
+// 設定ファイルの内容。ない項目はデフォルトの設定の値を使う
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
 struct SettingsInJson {
+    // 読み込んだファイルの形式。保存する時も同じ形式で書く
+    #[serde(skip)]
+    format: SettingsFormat,
+
+    // 設定ファイルの形式のバージョン
+    version: u64,
+
     asset_folder: String,
 
     // r g b (0 - 255)
@@ -145,12 +381,33 @@ struct SettingsInJson {
     tile_padding: f64,
     tile_background_color: Vec<f32>,
     tiles_colors: Vec<Vec<f32>>,
-    tile_unknow_color: Vec<f32>,
+    tile_unknown_color: Vec<f32>,
 
     tile_move_time: f64,
     tile_new_time: f64,
     tile_combine_time: f64,
 
+    // このスコアのタイルを作ると勝ち
+    target_tile: i32,
+    // 1ゲームで元に戻せる回数。負の値は無制限
+    undo_limit: i32,
+    // アニメーション中に入力した向きを、いくつまで順番待ちにするか
+    input_queue_size: i32,
+    // アニメーション中に入力した場合、順番待ちにせずアニメーションを終わらせてすぐ動かす
+    snap_animations: bool,
+
+    key_bindings: KeyBindingsInJson,
+    // マウスのドラッグでタイルを動かすのに必要な距離 (ピクセル)
+    swipe_min_distance: f64,
+
+    // スティックの傾きを無視する範囲 (0.0 - 1.0)
+    gamepad_dead_zone: f64,
+    // 向きを押し続けた時に繰り返す間隔 (秒)。0 以下は繰り返さない
+    gamepad_repeat_delay: f64,
+    // 元に戻す、新しいゲームを始めるボタンの番号。番号の並びは gamepad.rs を参照
+    gamepad_undo_button: i32,
+    gamepad_restart_button: i32,
+
     best_rect: Vec<f64>,
     score_rect: Vec<f64>,
 
@@ -160,30 +417,103 @@ struct SettingsInJson {
     text_light_color: Vec<f32>,
 }
 
+impl Default for SettingsInJson {
+    fn default() -> SettingsInJson {
+        SettingsInJson::default_settings()
+    }
+}
+
+// キーの割り当て
+//   preset: "arrows", "wasd", "hjkl" のどれか
+//   各操作: Piston のキーの名前の一覧 (例: ["W", "Up"])。空の場合はプリセットのキーを使う
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+struct KeyBindingsInJson {
+    preset: String,
+    up: Vec<String>,
+    down: Vec<String>,
+    left: Vec<String>,
+    right: Vec<String>,
+    restart: Vec<String>,
+    undo: Vec<String>,
+    redo: Vec<String>,
+    hint: Vec<String>,
+    pause: Vec<String>,
+    quit: Vec<String>,
+    keep_going: Vec<String>,
+    autoplay: Vec<String>,
+    strategy: Vec<String>,
+}
+
+impl Default for KeyBindingsInJson {
+    fn default() -> KeyBindingsInJson {
+        KeyBindingsInJson {
+            preset: "arrows".to_string(),
+            up: Vec::new(),
+            down: Vec::new(),
+            left: Vec::new(),
+            right: Vec::new(),
+            restart: Vec::new(),
+            undo: Vec::new(),
+            redo: Vec::new(),
+            hint: Vec::new(),
+            pause: Vec::new(),
+            quit: Vec::new(),
+            keep_going: Vec::new(),
+            autoplay: Vec::new(),
+            strategy: Vec::new(),
+        }
+    }
+}
+
+impl KeyBindingsInJson {
+    fn to_key_bindings(&self) -> Result<KeyBindings, String> {
+        let overrides: Vec<(Action, &[String])> = vec![
+            (Action::Up, &self.up),
+            (Action::Down, &self.down),
+            (Action::Left, &self.left),
+            (Action::Right, &self.right),
+            (Action::Restart, &self.restart),
+            (Action::Undo, &self.undo),
+            (Action::Redo, &self.redo),
+            (Action::Hint, &self.hint),
+            (Action::Pause, &self.pause),
+            (Action::Quit, &self.quit),
+            (Action::KeepGoing, &self.keep_going),
+            (Action::Autoplay, &self.autoplay),
+            (Action::Strategy, &self.strategy),
+        ];
+        KeyBindings::from_names(&self.preset, &overrides)
+    }
+}
+
 impl SettingsInJson {
     pub fn default_settings() -> SettingsInJson {
-        let mut tiles_colors = Vec::<Vec<f32>>::new();
-        // empty color
-        tiles_colors.push(vec![204.0, 192.0, 179.0]);
-        // 2 color
-        tiles_colors.push(vec![238.0, 228.0, 218.0]);
-        // 4 color
-        tiles_colors.push(vec![237.0, 224.0, 200.0]);
-        // 8 color
-        tiles_colors.push(vec![242.0, 177.0, 121.0]);
-        // 16 color
-        tiles_colors.push(vec![245.0, 149.0, 99.0]);
-        // 32 color
-        tiles_colors.push(vec![246.0, 124.0, 95.0]);
-        // 64 color
-        tiles_colors.push(vec![246.0, 94.0, 59.0]);
-        // 128 color
-        tiles_colors.push(vec![237.0, 207.0, 114.0]);
-        // 256 color
-        tiles_colors.push(vec![237.0, 204.0, 97.0]);
-        // 512 color
-        tiles_colors.push(vec![237.0, 200.0, 80.0]);
+        let tiles_colors = vec![
+            // empty color
+            vec![204.0, 192.0, 179.0],
+            // 2 color
+            vec![238.0, 228.0, 218.0],
+            // 4 color
+            vec![237.0, 224.0, 200.0],
+            // 8 color
+            vec![242.0, 177.0, 121.0],
+            // 16 color
+            vec![245.0, 149.0, 99.0],
+            // 32 color
+            vec![246.0, 124.0, 95.0],
+            // 64 color
+            vec![246.0, 94.0, 59.0],
+            // 128 color
+            vec![237.0, 207.0, 114.0],
+            // 256 color
+            vec![237.0, 204.0, 97.0],
+            // 512 color
+            vec![237.0, 200.0, 80.0],
+        ];
         SettingsInJson {
+            format: SettingsFormat::Json,
+            version: SETTINGS_VERSION,
             asset_folder: "bin/assets".to_string(),
             window_background_color: vec![255.0, 248.0, 239.0],
             comment1_offset_y: 72.0,
@@ -196,10 +526,21 @@ impl SettingsInJson {
             tile_padding: 16.0,
             tile_background_color: vec![187.0, 173.0, 160.0],
             tiles_colors: tiles_colors,
-            tile_unknow_color: vec![200.0, 0.0, 0.0],
+            tile_unknown_color: vec![200.0, 0.0, 0.0],
             tile_move_time: 0.1,
             tile_new_time: 0.1,
             tile_combine_time: 0.1,
+            target_tile: 2048,
+            undo_limit: -1,
+            input_queue_size: 2,
+            snap_animations: false,
+            key_bindings: KeyBindingsInJson::default(),
+            swipe_min_distance: 40.0,
+            gamepad_dead_zone: 0.5,
+            gamepad_repeat_delay: 0.25,
+            // B ボタン (右) と Y ボタン (上)
+            gamepad_undo_button: 1,
+            gamepad_restart_button: 3,
             best_rect: vec![284.0, 12.0, 96.0, 48.0,],
             score_rect: vec![176.0, 12.0, 96.0, 48.0],
             label_color: vec![187.0, 173.0, 160.0],
@@ -209,43 +550,136 @@ impl SettingsInJson {
         }
     }
 
-    pub fn load() -> SettingsInJson {
-        // 読み取り可能なファイルパスをResult型で返す
-        let exe_path = current_exe();
+    pub fn load() -> Result<SettingsInJson, SettingsError> {
+        // ファイルが存在しない場合、デフォルト設定を返す。JSON のファイルとして作成しておく
+        let (path, format) = match find_settings_file() {
+            Some(found) => found,
+            None => {
+                println!("Configuration file can't be found. Try to generate a default {}.", SettingsFormat::Json.file_name());
+                let default = SettingsInJson::default_settings();
+                default.save();
+                return Ok(default);
+            },
+        };
+        for &other in SettingsFormat::ALL.iter().filter(|&&other| other != format) {
+            if path_beside_settings(&other.file_name()).is_some_and(|path| path.exists()) {
+                println!("WARNING: {} is ignored because {} is used.", other.file_name(), format.file_name());
+            }
+        }
 
-        // 読み取り可能なファイルパスの取得エラー時はデフォルトの設定を使う
-        if exe_path.is_err() {
-            return SettingsInJson::default_settings();
+        let text = fs::read_to_string(&path).map_err(|e| SettingsError::Io(format, e))?;
+        let (settings, unknown_fields) = SettingsInJson::from_text(&text, format)?;
+        // 知らない項目は書き間違いの可能性があるため、警告だけして読み込みは続ける
+        for field in unknown_fields.iter() {
+            eprintln!("WARNING: Unknown field '{}' in {} is ignored.", field, format.file_name());
         }
+        Ok(settings)
+    }
 
-        // 読み取り可能なファイルパスを取得
-        let mut exe_path = exe_path.unwrap();
-        // 読み取り可能なファイルパスを親のパスに変更
-        exe_path.pop();
-        // 読み取り可能なファイルパスのファイル名を設定
-        let path = exe_path.join(Path::new(SETTING_FILENAME));
+    // 文字列から読み込む。古い形式は今の形式に変換する
+    // 設定と、無視した知らない項目のパスの一覧を返す
+    fn from_text(text: &str, format: SettingsFormat) -> Result<(SettingsInJson, Vec<String>), SettingsError> {
+        let mut value = format.parse(text).map_err(|e| SettingsError::Parse(format, e))?;
+        migrate(&mut value, format)?;
 
-        // ファイルの読み込み準備
-        let file = File::open(&path);
+        let mut unknown_fields = Vec::new();
+        let mut on_unknown = |path: serde_ignored::Path| unknown_fields.push(path.to_string());
+        let deserializer = serde_ignored::Deserializer::new(value.clone(), &mut on_unknown);
+        let mut settings: SettingsInJson = serde_path_to_error::deserialize(deserializer)
+            .map_err(|e| {
+                // 最初のエラーで止まるため、項目ごとに読み込み直して型が違う項目を全て集める
+                let mut problems = decode_problems(&value);
+                if problems.is_empty() {
+                    problems.push((e.path().to_string(), e.inner().to_string()));
+                }
+                SettingsError::Decode(format, problems)
+            })?;
+        settings.format = format;
+        Ok((settings, unknown_fields))
+    }
 
-        // ファイルが存在しない場合、デフォルト設定を返す。ファイルとして作成しておく
-        match file {
-            Err(e) => {
-                println!("Configuration file can't be open ({}). Try to generate a default one.", e);
-                let default = SettingsInJson::default_settings();
-                default.save();
-                return default;
-            },
-            _ => {}
+    // 全ての項目を確かめて、問題を全てまとめて返す
+    fn validate(&self) -> Result<(), SettingsError> {
+        let mut problems = Vec::<(String, String)>::new();
+        let mut problem = |path: &str, message: String| problems.push((path.to_string(), message));
+
+        // r g b の3つの値で、それぞれ 0 - 255
+        let mut check_color = |path: &str, color: &[f32]| {
+            if color.len() != 3 {
+                problem(path, format!("expected 3 values (r, g, b), got {}", color.len()));
+            } else if color.iter().any(|&v| !(0.0..=255.0).contains(&v)) {
+                problem(path, format!("values must be between 0 and 255, got {:?}", color));
+            }
+        };
+        check_color("window_background_color", &self.window_background_color);
+        check_color("tile_background_color", &self.tile_background_color);
+        check_color("tile_unknown_color", &self.tile_unknown_color);
+        check_color("label_color", &self.label_color);
+        check_color("button_color", &self.button_color);
+        check_color("text_dark_color", &self.text_dark_color);
+        check_color("text_light_color", &self.text_light_color);
+        for (i, color) in self.tiles_colors.iter().enumerate() {
+            check_color(&format!("tiles_colors[{}]", i), color);
+        }
+        if self.tiles_colors.is_empty() {
+            problem("tiles_colors", "needs at least the color of empty cells".to_string());
         }
 
-        // BufferReaderを使う準備
-        let mut reader = BufReader::new(file.unwrap());
+        if let Err(e) = check_board_size(self.tile_width, self.tile_height) {
+            problem("tile_width/tile_height", e);
+        }
+        if self.tile_size <= 0.0 {
+            problem("tile_size", format!("must be positive, got {}", self.tile_size));
+        }
+        for (path, value) in [
+            ("board_padding", self.board_padding),
+            ("board_offset_y", self.board_offset_y),
+            ("tile_padding", self.tile_padding),
+            ("tile_move_time", self.tile_move_time),
+            ("tile_new_time", self.tile_new_time),
+            ("tile_combine_time", self.tile_combine_time),
+            ("swipe_min_distance", self.swipe_min_distance),
+            ("gamepad_repeat_delay", self.gamepad_repeat_delay),
+        ].iter() {
+            if *value < 0.0 {
+                problem(path, format!("must not be negative, got {}", value));
+            }
+        }
+        if self.target_tile < 2 || self.target_tile.count_ones() != 1 {
+            problem("target_tile", format!("must be a power of two (2, 4, 8, ...), got {}", self.target_tile));
+        } else if check_board_size(self.tile_width, self.tile_height).is_ok()
+            && self.target_tile > largest_possible_tile(self.tile_width, self.tile_height) {
+            problem("target_tile", format!("can't be reached on a {}x{} board, the largest tile is {}",
+                self.tile_width, self.tile_height, largest_possible_tile(self.tile_width, self.tile_height)));
+        }
+        if self.input_queue_size < 0 {
+            problem("input_queue_size", format!("must not be negative, got {}", self.input_queue_size));
+        }
+        if !(0.0..1.0).contains(&self.gamepad_dead_zone) {
+            problem("gamepad_dead_zone", format!("must be between 0.0 and 1.0, got {}", self.gamepad_dead_zone));
+        }
+        for (path, button) in [("gamepad_undo_button", self.gamepad_undo_button), ("gamepad_restart_button", self.gamepad_restart_button)].iter() {
+            if !is_action_button(*button) {
+                problem(path, format!("must be a button number from 0 to 10 (11 - 14 are the D-pad), got {}", button));
+            }
+        }
+        if self.gamepad_undo_button == self.gamepad_restart_button {
+            problem("gamepad_restart_button", format!("must differ from gamepad_undo_button, both are {}", self.gamepad_undo_button));
+        }
+        for (path, rect) in [("best_rect", &self.best_rect), ("score_rect", &self.score_rect)].iter() {
+            if rect.len() != 4 {
+                problem(path, format!("expected 4 values (x, y, width, height), got {}", rect.len()));
+            }
+        }
+        if let Err(e) = self.key_bindings.to_key_bindings() {
+            problem("key_bindings", e);
+        }
 
-        // ファイルの中身をデシリアライズするためのデコーダを準備
-        let mut decoder = json::Decoder::new(json::Json::from_reader(&mut reader).unwrap());
-        // デコードする
-        Decodable::decode(&mut decoder).unwrap()
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(SettingsError::Invalid(self.format, problems))
+        }
     }
 
     pub fn save(&self) {
@@ -258,25 +692,245 @@ impl SettingsInJson {
             return;
         }
 
-        // 書き込み可能なファイルを用意して、書き込み準備
+        // 書き込み可能なファイルを用意して、書き込み準備。読み込んだ時と同じ形式で書く
         let path = exe_path.unwrap();
-        let file = File::create(&path.with_file_name(SETTING_FILENAME)).unwrap();
+        let file = match File::create(path.with_file_name(self.format.file_name())) {
+            Ok(file) => file,
+            Err(e) => {
+                println!("WARNING: Failed to save settings: {}", e);
+                return;
+            }
+        };
         let mut writer = BufWriter::new(file);
 
         // 設定ファイルへ書き込み
-        // SettingsInJsonが正しくエンコードできるフォーマットかどうか
-        match json::encode(self) {
-            // 正しくエンコードできる場合
-            Ok(encoded) => {
-                // ファイル書き込みにエラーが起きた場合
-                if let Err(e) = writer.write(encoded.as_bytes()) {
-                    println!("WARNING: Failed to save settings: {}", e);
-                }
+        let result = self.format.encode(self)
+            .and_then(|encoded| writer.write_all(encoded.as_bytes()).map_err(|e| e.to_string()));
+        if let Err(e) = result {
+            println!("WARNING: Failed to save settings: {}", e);
+        }
+    }
+}
+
+// version を見て、古い形式の設定を順に今の形式へ変換する。version がないファイルは 1 とみなす
+// 項目を1つずつ、他の項目はデフォルトのまま読み込んで、読めない項目の (パス, 内容) を返す
+fn decode_problems(value: &Value) -> Vec<(String, String)> {
+    let fields = match value.as_object() {
+        Some(fields) => fields,
+        None => return Vec::new(),
+    };
+    fields.iter()
+        .filter_map(|(key, field)| {
+            let mut single = serde_json::Map::new();
+            single.insert(key.clone(), field.clone());
+            serde_path_to_error::deserialize::<_, SettingsInJson>(Value::Object(single))
+                .err()
+                .map(|e| (e.path().to_string(), e.inner().to_string()))
+        })
+        .collect()
+}
+
+fn migrate(value: &mut Value, format: SettingsFormat) -> Result<(), SettingsError> {
+    let fields = match value.as_object_mut() {
+        Some(fields) => fields,
+        // オブジェクトでない場合は、デコードでエラーにする
+        None => return Ok(()),
+    };
+    let version = match fields.get("version") {
+        None => 1,
+        Some(version) => version.as_u64()
+            .filter(|&version| version >= 1)
+            .ok_or_else(|| SettingsError::Decode(format, vec![("version".to_string(), format!("expected a positive integer, got {}", version))]))?,
+    };
+    if version > SETTINGS_VERSION {
+        return Err(SettingsError::UnsupportedVersion(format, version));
+    }
+
+    for migration in MIGRATIONS[(version - 1) as usize..].iter() {
+        migration(fields);
+    }
+    fields.insert("version".to_string(), Value::from(SETTINGS_VERSION));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_settings_are_valid() {
+        assert!(SettingsInJson::default_settings().validate().is_ok());
+    }
+
+    #[test]
+    fn reports_every_problem_with_its_path() {
+        let mut s = SettingsInJson::default_settings();
+        s.window_background_color = vec![255.0, 248.0];
+        s.tiles_colors[2] = vec![237.0, 224.0, 300.0];
+        s.tile_width = 0;
+        s.tile_size = -1.0;
+        s.best_rect = vec![1.0, 2.0];
+        s.key_bindings.undo = vec!["Space".to_string()];
+        s.gamepad_undo_button = 12;
+        s.gamepad_restart_button = 12;
+
+        let problems = match s.validate() {
+            Err(SettingsError::Invalid(_, problems)) => problems,
+            other => panic!("expected invalid settings, got {:?}", other),
+        };
+        let paths: Vec<&str> = problems.iter().map(|(path, _)| path.as_str()).collect();
+        assert_eq!(paths, vec![
+            "window_background_color",
+            "tiles_colors[2]",
+            "tile_width/tile_height",
+            "tile_size",
+            "gamepad_undo_button",
+            "gamepad_restart_button",
+            "gamepad_restart_button",
+            "best_rect",
+            "key_bindings",
+        ]);
+
+        let message = SettingsError::Invalid(SettingsFormat::Json, problems).to_string();
+        assert!(message.contains("9 invalid value(s)"), "{}", message);
+        assert!(message.contains("tiles_colors[2]: values must be between 0 and 255"), "{}", message);
+    }
+
+    #[test]
+    fn rejects_unreachable_target_tiles() {
+        // 1x2 の盤面では 8 までしか作れない
+        let mut s = SettingsInJson::default_settings();
+        s.tile_width = 1;
+        s.tile_height = 2;
+        s.target_tile = 8;
+        assert!(s.validate().is_ok());
+        s.target_tile = 16;
+        match s.validate() {
+            Err(SettingsError::Invalid(_, problems)) => assert_eq!(problems[0].0, "target_tile"),
+            other => panic!("expected invalid settings, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rejects_empty_tile_colors() {
+        let mut s = SettingsInJson::default_settings();
+        s.tiles_colors.clear();
+        match s.validate() {
+            Err(SettingsError::Invalid(_, problems)) => assert_eq!(problems[0].0, "tiles_colors"),
+            other => panic!("expected invalid settings, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn partial_files_use_the_defaults() {
+        let (s, unknown_fields) = SettingsInJson::from_text(r#"{ "version": 2, "tile_width": 5, "key_bindings": { "preset": "wasd" } }"#, SettingsFormat::Json).unwrap();
+        assert!(unknown_fields.is_empty());
+        assert_eq!(s.tile_width, 5);
+        assert_eq!(s.tile_height, 4);
+        assert_eq!(s.key_bindings.preset, "wasd");
+        assert!(s.key_bindings.up.is_empty());
+        assert!(s.validate().is_ok());
+
+        // 型が違う項目は全て報告する
+        match SettingsInJson::from_text(r#"{ "tile_size": "big", "tile_width": 5, "key_bindings": { "up": "W" }, "undo_limit": 1.5 }"#, SettingsFormat::Json) {
+            Err(SettingsError::Decode(_, problems)) => {
+                let paths: Vec<&str> = problems.iter().map(|(path, _)| path.as_str()).collect();
+                assert_eq!(paths, vec!["key_bindings.up", "tile_size", "undo_limit"]);
             },
-            // 正しくエンコードできない場合
-            Err(e) => {
-                println!("WARNING: Failed to save settings: {}", e);
-            }
+            _ => panic!("expected a decode error"),
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn migrates_old_layouts_and_rejects_newer_ones() {
+        // version がないファイルは最初の形式
+        let (s, _) = SettingsInJson::from_text(r#"{ "tile_unknow_color": [1, 2, 3] }"#, SettingsFormat::Json).unwrap();
+        assert_eq!(s.version, SETTINGS_VERSION);
+        assert_eq!(s.tile_unknown_color, vec![1.0, 2.0, 3.0]);
+
+        match SettingsInJson::from_text(&format!(r#"{{ "version": {} }}"#, SETTINGS_VERSION + 1), SettingsFormat::Json) {
+            Err(SettingsError::UnsupportedVersion(_, version)) => assert_eq!(version, SETTINGS_VERSION + 1),
+            _ => panic!("expected an unsupported version"),
+        }
+    }
+
+    #[test]
+    fn reports_unknown_fields() {
+        let (s, unknown_fields) = SettingsInJson::from_text(
+            r#"{ "version": 2, "tile_widht": 5, "key_bindings": { "jump": ["Space"] } }"#, SettingsFormat::Json).unwrap();
+        assert_eq!(unknown_fields, vec!["key_bindings.jump", "tile_widht"]);
+        assert_eq!(s.tile_width, 4);
+    }
+
+    #[test]
+    fn reads_commented_toml_and_ron() {
+        let toml = r#"
+            # 5x5 の盤面
+            version = 2
+            tile_width = 5
+            tile_unknown_color = [10, 20, 30]
+
+            [key_bindings]
+            preset = "hjkl" # vim のキー
+        "#;
+        let (s, unknown_fields) = SettingsInJson::from_text(toml, SettingsFormat::Toml).unwrap();
+        assert!(unknown_fields.is_empty());
+        assert_eq!(s.format, SettingsFormat::Toml);
+        assert_eq!(s.tile_width, 5);
+        assert_eq!(s.tile_unknown_color, vec![10.0, 20.0, 30.0]);
+        assert_eq!(s.key_bindings.preset, "hjkl");
+
+        let ron = r#"(
+            // 5x5 の盤面
+            version: 2,
+            tile_width: 5,
+            key_bindings: (preset: "wasd"),
+        )"#;
+        let (s, unknown_fields) = SettingsInJson::from_text(ron, SettingsFormat::Ron).unwrap();
+        assert!(unknown_fields.is_empty());
+        assert_eq!(s.format, SettingsFormat::Ron);
+        assert_eq!(s.tile_width, 5);
+        assert_eq!(s.key_bindings.preset, "wasd");
+
+        match SettingsInJson::from_text("tile_width = ", SettingsFormat::Toml) {
+            Err(e @ SettingsError::Parse(..)) => assert!(e.to_string().starts_with("settings.toml is not valid TOML"), "{}", e),
+            _ => panic!("expected a parse error"),
+        }
+    }
+
+    #[test]
+    fn every_format_reads_back_what_it_writes() {
+        for &format in SettingsFormat::ALL.iter() {
+            let mut s = SettingsInJson::default_settings();
+            s.tile_width = 6;
+            s.key_bindings.undo = vec!["Z".to_string()];
+            let encoded = format.encode(&s).unwrap();
+
+            let (decoded, unknown_fields) = SettingsInJson::from_text(&encoded, format).unwrap();
+            assert!(unknown_fields.is_empty(), "{:?}: {:?}", format, unknown_fields);
+            assert_eq!(format.encode(&decoded).unwrap(), encoded, "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn reload_keeps_the_rules_of_the_game() {
+        let current = Settings::default_settings();
+
+        // 見た目や操作の設定は変えられる
+        let mut new = Settings::default_settings();
+        new.tile_move_time *= 2.0;
+        new.swipe_min_distance += 10.0;
+        assert!(current.check_reload(&new).is_ok());
+
+        // fingerprint に入る設定は変えられない
+        let mut new = Settings::default_settings();
+        new.target_tile *= 2;
+        let error = current.check_reload(&new).unwrap_err();
+        assert!(error.contains("target_tile 2048 to 4096"), "{}", error);
+
+        let mut new = Settings::default_settings();
+        new.tile_width += 1;
+        let error = current.check_reload(&new).unwrap_err();
+        assert!(error.contains("board size 4x4 to 5x4"), "{}", error);
+    }
+}
@@ -0,0 +1,82 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use crate::settings::{find_settings_file, Settings, SettingsError};
+
+// 設定ファイルを確かめる間隔 (秒)
+static POLL_INTERVAL: f64 = 1.0;
+
+// 設定ファイルの更新日時を定期的に確かめて、変わったら読み込み直す
+pub struct SettingsWatcher {
+    elapsed: f64,
+    // 最後に確かめた設定ファイルのパスと更新日時。ファイルがない場合は None
+    last: Option<(PathBuf, SystemTime)>,
+}
+
+impl SettingsWatcher {
+    pub fn new() -> SettingsWatcher {
+        SettingsWatcher {
+            elapsed: 0.0,
+            last: current_file(),
+        }
+    }
+
+    // 時間を進めて、設定ファイルが変わっていた場合は読み込み直した結果を返す
+    pub fn poll(&mut self, dt: f64) -> Option<Result<Settings, SettingsError>> {
+        self.elapsed += dt;
+        if self.elapsed < POLL_INTERVAL {
+            return None;
+        }
+        self.elapsed = 0.0;
+
+        if self.changed(current_file()) {
+            Some(Settings::try_load())
+        } else {
+            None
+        }
+    }
+
+    // ファイルを消した場合は、今の設定のまま続ける
+    fn changed(&mut self, current: Option<(PathBuf, SystemTime)>) -> bool {
+        if current == self.last {
+            return false;
+        }
+        self.last = current;
+        self.last.is_some()
+    }
+}
+
+impl Default for SettingsWatcher {
+    fn default() -> SettingsWatcher {
+        SettingsWatcher::new()
+    }
+}
+
+fn current_file() -> Option<(PathBuf, SystemTime)> {
+    let (path, _) = find_settings_file()?;
+    let modified = fs::metadata(&path).and_then(|metadata| metadata.modified()).ok()?;
+    Some((path, modified))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn reloads_when_the_file_changes_or_reappears() {
+        let file = |name: &str, seconds: u64| Some((PathBuf::from(name), SystemTime::UNIX_EPOCH + Duration::from_secs(seconds)));
+        let mut watcher = SettingsWatcher {
+            elapsed: 0.0,
+            last: file("settings.json", 1),
+        };
+
+        assert!(!watcher.changed(file("settings.json", 1)));
+        assert!(watcher.changed(file("settings.json", 2)));
+        assert!(!watcher.changed(file("settings.json", 2)));
+        // 消した後に別の形式のファイルを置いた場合
+        assert!(!watcher.changed(None));
+        assert!(watcher.changed(file("settings.toml", 2)));
+    }
+}
@@ -0,0 +1,146 @@
+use std::cell::RefCell;
+use rand::Rng;
+use crate::{ai, game::{Direction, GameState}, rng::GameRng};
+
+// 盤面から次に動かす向きを選ぶ戦略
+pub trait Strategy {
+    fn name(&self) -> &str;
+    // 動かせる向きがない場合は、どの向きを返してもよい
+    fn choose(&self, game: &GameState) -> Direction;
+}
+
+// 盤面が変化する向きの一覧
+pub fn legal_directions(game: &GameState) -> Vec<Direction> {
+    Direction::ALL.iter()
+        .cloned()
+        .filter(|&direction| game.clone().slide(direction).moved)
+        .collect()
+}
+
+pub static STRATEGY_NAMES: [&str; 3] = ["random", "corner", "expectimax"];
+
+// 名前から戦略を作る。rng を使う戦略はシードで再現できる
+pub fn strategy_by_name(name: &str, seed: u64) -> Option<Box<dyn Strategy>> {
+    match name {
+        "random" => Some(Box::new(RandomStrategy::new(seed))),
+        "corner" => Some(Box::new(CornerGreedyStrategy)),
+        "expectimax" => Some(Box::new(ExpectimaxStrategy::new(2))),
+        _ => None,
+    }
+}
+
+// 動かせる向きからランダムに選ぶ
+pub struct RandomStrategy {
+    rng: RefCell<GameRng>,
+}
+
+impl RandomStrategy {
+    pub fn new(seed: u64) -> RandomStrategy {
+        RandomStrategy {
+            rng: RefCell::new(GameRng::new(seed)),
+        }
+    }
+}
+
+impl Strategy for RandomStrategy {
+    fn name(&self) -> &str {
+        "random"
+    }
+
+    fn choose(&self, game: &GameState) -> Direction {
+        let directions = legal_directions(game);
+        if directions.is_empty() {
+            return Direction::Up;
+        }
+        let i = self.rng.borrow_mut().gen_range(0, directions.len());
+        directions[i]
+    }
+}
+
+// 大きいタイルを左下の角に集める。下と左の中で一番スコアが増える向きを選び、
+// どちらも動かせない場合は右、最後に上を選ぶ
+pub struct CornerGreedyStrategy;
+
+impl Strategy for CornerGreedyStrategy {
+    fn name(&self) -> &str {
+        "corner"
+    }
+
+    fn choose(&self, game: &GameState) -> Direction {
+        let mut best: Option<(Direction, i32)> = None;
+        for &direction in [Direction::Down, Direction::Left].iter() {
+            let outcome = game.clone().slide(direction);
+            if outcome.moved && best.is_none_or(|(_, score)| outcome.score_gained > score) {
+                best = Some((direction, outcome.score_gained));
+            }
+        }
+
+        match best {
+            Some((direction, _)) => direction,
+            None if game.clone().slide(Direction::Right).moved => Direction::Right,
+            None => Direction::Up,
+        }
+    }
+}
+
+// expectimax で探索する
+pub struct ExpectimaxStrategy {
+    depth: u32,
+}
+
+impl ExpectimaxStrategy {
+    pub fn new(depth: u32) -> ExpectimaxStrategy {
+        ExpectimaxStrategy {
+            depth: depth,
+        }
+    }
+}
+
+impl Strategy for ExpectimaxStrategy {
+    fn name(&self) -> &str {
+        "expectimax"
+    }
+
+    fn choose(&self, game: &GameState) -> Direction {
+        ai::best_direction(game, self.depth).unwrap_or(Direction::Up)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 左だけ盤面が変わる
+    fn only_left_moves() -> GameState {
+        GameState::from_rows(&[
+            &[0, 2, 4, 8],
+            &[0, 4, 8, 16],
+            &[0, 2, 4, 8],
+            &[0, 4, 8, 16],
+        ])
+    }
+
+    #[test]
+    fn lists_legal_directions() {
+        assert_eq!(legal_directions(&only_left_moves()), vec![Direction::Left]);
+    }
+
+    #[test]
+    fn every_strategy_picks_the_only_legal_move() {
+        let game = only_left_moves();
+        for name in STRATEGY_NAMES.iter() {
+            let strategy = strategy_by_name(name, 7).unwrap();
+            assert_eq!(strategy.choose(&game), Direction::Left, "{}", name);
+        }
+    }
+
+    #[test]
+    fn random_strategy_repeats_with_the_same_seed() {
+        let game = GameState::new(4, 4, GameRng::new(3));
+        let a = RandomStrategy::new(42);
+        let b = RandomStrategy::new(42);
+        for _ in 0..20 {
+            assert_eq!(a.choose(&game), b.choose(&game));
+        }
+    }
+}
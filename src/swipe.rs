@@ -0,0 +1,140 @@
+use crate::game::Direction;
+
+// スクロールが止まってから、次のスワイプとみなすまでの時間 (秒)
+static SCROLL_GESTURE_GAP: f64 = 0.2;
+
+// マウスのドラッグやタッチパッドのスワイプから、タイルを動かす向きを判定する
+//
+// ボタンを押した時と離した時のカーソルの位置の差のうち、大きい方の軸の向きにする
+// 移動距離が min_distance より短い場合はクリックとみなして何もしない
+//
+// タッチパッドの2本指のスワイプはスクロールのイベントになるため、移動量を足していき、
+// min_distance を超えた時に1回だけ動かす。スクロールが SCROLL_GESTURE_GAP 秒止まったら次のスワイプになる
+pub struct Swipe {
+    min_distance: f64,
+    // 最後に分かったカーソルの位置。押した・離したイベントには位置がないため
+    cursor: [f64; 2],
+    // ボタンを押した時の位置
+    start: Option<[f64; 2]>,
+    // 今のスクロールの移動量の合計と、最後のスクロールからの経過時間
+    scroll: [f64; 2],
+    scroll_idle: f64,
+    // 今のスクロールで既に動かしたか
+    scroll_done: bool,
+}
+
+impl Swipe {
+    pub fn new(min_distance: f64) -> Swipe {
+        Swipe {
+            min_distance: min_distance,
+            cursor: [0.0, 0.0],
+            start: None,
+            scroll: [0.0, 0.0],
+            scroll_idle: 0.0,
+            scroll_done: false,
+        }
+    }
+
+    pub fn motion(&mut self, position: [f64; 2]) {
+        self.cursor = position;
+    }
+
+    pub fn press(&mut self) {
+        self.start = Some(self.cursor);
+    }
+
+    // ボタンを離した時のスワイプの向き
+    pub fn release(&mut self) -> Option<Direction> {
+        let start = self.start.take()?;
+        self.direction(self.cursor[0] - start[0], self.cursor[1] - start[1])
+    }
+
+    // スクロールの移動量。内容を動かす向き (y は下向きが正) にタイルを動かす
+    // タッチパッドのナチュラルスクロールでは、指を動かした向きになる
+    pub fn scroll(&mut self, delta: [f64; 2]) -> Option<Direction> {
+        self.scroll_idle = 0.0;
+        if self.scroll_done {
+            return None;
+        }
+        self.scroll = [self.scroll[0] + delta[0], self.scroll[1] + delta[1]];
+        let direction = self.direction(self.scroll[0], self.scroll[1]);
+        self.scroll_done = direction.is_some();
+        direction
+    }
+
+    // 時間を進める。スクロールが止まっていたら次のスワイプの準備をする
+    pub fn update(&mut self, dt: f64) {
+        self.scroll_idle += dt;
+        if self.scroll_idle >= SCROLL_GESTURE_GAP {
+            self.scroll = [0.0, 0.0];
+            self.scroll_done = false;
+        }
+    }
+
+    fn direction(&self, dx: f64, dy: f64) -> Option<Direction> {
+        if dx.abs().max(dy.abs()) < self.min_distance {
+            return None;
+        }
+        // 画面の y 座標は下向き
+        if dx.abs() > dy.abs() {
+            Some(if dx > 0.0 { Direction::Right } else { Direction::Left })
+        } else {
+            Some(if dy > 0.0 { Direction::Down } else { Direction::Up })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn swipe(from: [f64; 2], to: [f64; 2]) -> Option<Direction> {
+        let mut swipe = Swipe::new(30.0);
+        swipe.motion(from);
+        swipe.press();
+        swipe.motion([(from[0] + to[0]) / 2.0, (from[1] + to[1]) / 2.0]);
+        swipe.motion(to);
+        swipe.release()
+    }
+
+    #[test]
+    fn detects_the_dominant_axis() {
+        assert_eq!(swipe([100.0, 100.0], [100.0, 40.0]), Some(Direction::Up));
+        assert_eq!(swipe([100.0, 100.0], [110.0, 180.0]), Some(Direction::Down));
+        assert_eq!(swipe([100.0, 100.0], [20.0, 90.0]), Some(Direction::Left));
+        assert_eq!(swipe([100.0, 100.0], [160.0, 120.0]), Some(Direction::Right));
+    }
+
+    #[test]
+    fn ignores_short_drags_and_releases_without_press() {
+        assert_eq!(swipe([100.0, 100.0], [120.0, 110.0]), None);
+
+        let mut swipe = Swipe::new(30.0);
+        swipe.motion([0.0, 0.0]);
+        swipe.motion([100.0, 0.0]);
+        assert_eq!(swipe.release(), None);
+    }
+
+    #[test]
+    fn scrolling_moves_once_per_gesture() {
+        let mut swipe = Swipe::new(30.0);
+        assert_eq!(swipe.scroll([2.0, 10.0]), None);
+        assert_eq!(swipe.scroll([1.0, 15.0]), None);
+        assert_eq!(swipe.scroll([0.0, 8.0]), Some(Direction::Down));
+        // 同じスクロールの続きや慣性では動かさない
+        swipe.update(0.05);
+        assert_eq!(swipe.scroll([0.0, 40.0]), None);
+
+        // 止まってから次のスワイプ
+        swipe.update(SCROLL_GESTURE_GAP);
+        assert_eq!(swipe.scroll([-35.0, 5.0]), Some(Direction::Left));
+    }
+
+    #[test]
+    fn short_scrolls_are_forgotten() {
+        let mut swipe = Swipe::new(30.0);
+        assert_eq!(swipe.scroll([20.0, 0.0]), None);
+        swipe.update(SCROLL_GESTURE_GAP);
+        assert_eq!(swipe.scroll([20.0, 0.0]), None);
+    }
+}
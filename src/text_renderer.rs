@@ -0,0 +1,109 @@
+use piston_window::*;
+use opengl_graphics::GlGraphics;
+
+// 1ドットの大きさ
+static PIXEL_SIZE: f64 = 3.0;
+// 文字の幅と高さ (ドット数)
+static GLYPH_WIDTH: usize = 5;
+static GLYPH_HEIGHT: usize = 7;
+
+// 5x7ドットのフォント。各行の下位5ビットが左から右のドットに対応する
+static GLYPHS: &[(char, [u8; 7])] = &[
+    ('A', [0b01110, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001]),
+    ('B', [0b11110, 0b10001, 0b10001, 0b11110, 0b10001, 0b10001, 0b11110]),
+    ('C', [0b01110, 0b10001, 0b10000, 0b10000, 0b10000, 0b10001, 0b01110]),
+    ('D', [0b11110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b11110]),
+    ('E', [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b11111]),
+    ('F', [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b10000]),
+    ('G', [0b01110, 0b10001, 0b10000, 0b10111, 0b10001, 0b10001, 0b01111]),
+    ('H', [0b10001, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001]),
+    ('I', [0b01110, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110]),
+    ('J', [0b00111, 0b00010, 0b00010, 0b00010, 0b00010, 0b10010, 0b01100]),
+    ('K', [0b10001, 0b10010, 0b10100, 0b11000, 0b10100, 0b10010, 0b10001]),
+    ('L', [0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b11111]),
+    ('M', [0b10001, 0b11011, 0b10101, 0b10101, 0b10001, 0b10001, 0b10001]),
+    ('N', [0b10001, 0b10001, 0b11001, 0b10101, 0b10011, 0b10001, 0b10001]),
+    ('O', [0b01110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110]),
+    ('P', [0b11110, 0b10001, 0b10001, 0b11110, 0b10000, 0b10000, 0b10000]),
+    ('Q', [0b01110, 0b10001, 0b10001, 0b10001, 0b10101, 0b10010, 0b01101]),
+    ('R', [0b11110, 0b10001, 0b10001, 0b11110, 0b10100, 0b10010, 0b10001]),
+    ('S', [0b01111, 0b10000, 0b10000, 0b01110, 0b00001, 0b00001, 0b11110]),
+    ('T', [0b11111, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100]),
+    ('U', [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110]),
+    ('V', [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01010, 0b00100]),
+    ('W', [0b10001, 0b10001, 0b10001, 0b10101, 0b10101, 0b10101, 0b01010]),
+    ('X', [0b10001, 0b10001, 0b01010, 0b00100, 0b01010, 0b10001, 0b10001]),
+    ('Y', [0b10001, 0b10001, 0b01010, 0b00100, 0b00100, 0b00100, 0b00100]),
+    ('Z', [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b11111]),
+    ('0', [0b01110, 0b10001, 0b10011, 0b10101, 0b11001, 0b10001, 0b01110]),
+    ('1', [0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110]),
+    ('2', [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b01000, 0b11111]),
+    ('3', [0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110]),
+    ('4', [0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010]),
+    ('5', [0b11111, 0b10000, 0b11110, 0b00001, 0b00001, 0b10001, 0b01110]),
+    ('6', [0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110]),
+    ('7', [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000]),
+    ('8', [0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110]),
+    ('9', [0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100]),
+    (':', [0b00000, 0b01100, 0b01100, 0b00000, 0b01100, 0b01100, 0b00000]),
+    ('.', [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b01100]),
+    ('!', [0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00000, 0b00100]),
+    ('?', [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b00000, 0b00100]),
+    ('-', [0b00000, 0b00000, 0b00000, 0b11111, 0b00000, 0b00000, 0b00000]),
+    ('/', [0b00001, 0b00010, 0b00010, 0b00100, 0b01000, 0b01000, 0b10000]),
+    ('<', [0b00010, 0b00100, 0b01000, 0b10000, 0b01000, 0b00100, 0b00010]),
+    ('>', [0b01000, 0b00100, 0b00010, 0b00001, 0b00010, 0b00100, 0b01000]),
+];
+
+pub struct TextRenderer;
+
+impl TextRenderer {
+    pub fn new() -> TextRenderer {
+        TextRenderer
+    }
+
+    // NumberRenderer::render と同じ引数で呼べるようにする
+    #[allow(clippy::too_many_arguments)]
+    pub fn render(&self, text: &str, center_x: f64, center_y: f64, max_width: f64, color: [f32; 3], c: &Context, gl: &mut GlGraphics) {
+        // 文字と文字の間は1ドット空ける
+        let columns = (text.chars().count() * (GLYPH_WIDTH + 1)).saturating_sub(1);
+        if columns == 0 {
+            return;
+        }
+        // 合計幅が最大幅を超える場合、ドットを小さくする
+        let pixel = if columns as f64 * PIXEL_SIZE > max_width {
+            max_width / columns as f64
+        } else {
+            PIXEL_SIZE
+        };
+        // 書き込む文字列の座標を、中央に合わせる
+        let mut x = center_x - columns as f64 * pixel / 2.0;
+        let y = center_y - GLYPH_HEIGHT as f64 * pixel / 2.0;
+
+        let rect = Rectangle::new([color[0], color[1], color[2], 1.0]);
+        for ch in text.chars() {
+            // 小文字は大文字で描画する。フォントにない文字は空白として扱う
+            let ch = ch.to_ascii_uppercase();
+            if let Some((_, rows)) = GLYPHS.iter().find(|(g, _)| *g == ch) {
+                for (row, bits) in rows.iter().enumerate() {
+                    for col in 0..GLYPH_WIDTH {
+                        if bits & (1 << (GLYPH_WIDTH - 1 - col)) != 0 {
+                            rect.draw(
+                                [x + col as f64 * pixel, y + row as f64 * pixel, pixel, pixel],
+                                &DrawState::default(),
+                                c.transform,
+                                gl);
+                        }
+                    }
+                }
+            }
+            x += (GLYPH_WIDTH + 1) as f64 * pixel;
+        }
+    }
+}
+
+impl Default for TextRenderer {
+    fn default() -> TextRenderer {
+        TextRenderer::new()
+    }
+}
@@ -11,57 +11,62 @@ pub enum TileState {
     TileCombine(f64, f64),
 }
 
+// 設定は持たず、動かす時や描画する時に今の設定を受け取る。設定を読み込み直しても、そのまま使える
 #[derive(Clone, Debug)]
-pub struct Tile<'a> {
+pub struct Tile {
     pub score: i32,
     pub tile_x: i32,
     pub tile_y: i32,
     pub status: TileState,
-
-    settings: &'a Settings,
 }
 
-impl<'a> Tile<'a> {
-    pub fn new(settings: &'a Settings, score: i32, tile_x: i32, tile_y: i32) -> Tile<'a> {
+impl Tile {
+    pub fn new(settings: &Settings, score: i32, tile_x: i32, tile_y: i32) -> Tile {
         Tile {
             score: score,
             tile_x: tile_x,
             tile_y: tile_y,
             status: TileState::TileNew(settings.tile_new_time, 0.0),
+        }
+    }
 
-            settings: settings,
+    // アニメーションなしで置かれたタイル
+    pub fn new_static(score: i32, tile_x: i32, tile_y: i32) -> Tile {
+        Tile {
+            score: score,
+            tile_x: tile_x,
+            tile_y: tile_y,
+            status: TileState::TileStatic,
         }
     }
 
-    pub fn new_combined(settings: &'a Settings, score: i32, tile_x: i32, tile_y: i32) -> Tile<'a> {
+    pub fn new_combined(settings: &Settings, score: i32, tile_x: i32, tile_y: i32) -> Tile {
         Tile {
             score: score,
             tile_x: tile_x,
             tile_y: tile_y,
             status: TileState::TileCombine(settings.tile_combine_time, 1.2 * settings.tile_size),
-
-            settings: settings,
         }
     }
 
-    fn tile_to_pos(&self, tile_x: i32, tile_y: i32) -> (f64, f64) {
-        let x = self.settings.board_padding + tile_x as f64 * self.settings.tile_size + (tile_x + 1) as f64 * self.settings.tile_padding;
-        let y = self.settings.board_padding + self.settings.board_offset_y + tile_y as f64 * self.settings.tile_size + (tile_y + 1) as f64 * self.settings.tile_padding;
+    fn tile_to_pos(settings: &Settings, tile_x: i32, tile_y: i32) -> (f64, f64) {
+        let x = settings.board_padding + tile_x as f64 * settings.tile_size + (tile_x + 1) as f64 * settings.tile_padding;
+        let y = settings.board_padding + settings.board_offset_y + tile_y as f64 * settings.tile_size + (tile_y + 1) as f64 * settings.tile_padding;
         (x, y)
     }
 
     // タイルを動かす
-    pub fn start_moving(&mut self, destination_tile_x: i32, destination_tile_y: i32) {
+    pub fn start_moving(&mut self, settings: &Settings, destination_tile_x: i32, destination_tile_y: i32) {
         match self.status {
             TileState::TileMoving(_, _, _, ox, oy) => {
-                let (x, y) = self.tile_to_pos(ox, oy);
-                self.status = TileState::TileMoving(self.settings.tile_move_time, x, y, ox, oy);
+                let (x, y) = Tile::tile_to_pos(settings, ox, oy);
+                self.status = TileState::TileMoving(settings.tile_move_time, x, y, ox, oy);
                 self.tile_x = destination_tile_x;
                 self.tile_y = destination_tile_y;
             },
             TileState::TileStatic => {
-                let (x, y) = self.tile_to_pos(self.tile_x, self.tile_y);
-                self.status = TileState::TileMoving(self.settings.tile_move_time, x, y, self.tile_x, self.tile_y);
+                let (x, y) = Tile::tile_to_pos(settings, self.tile_x, self.tile_y);
+                self.status = TileState::TileMoving(settings.tile_move_time, x, y, self.tile_x, self.tile_y);
                 self.tile_x = destination_tile_x;
                 self.tile_y = destination_tile_y;
             },
@@ -69,13 +74,13 @@ impl<'a> Tile<'a> {
         }
     }
 
-    pub fn update(&mut self, dt: f64) {
+    pub fn update(&mut self, settings: &Settings, dt: f64) {
         match self.status {
             TileState::TileMoving(t, x, y, ox, oy) => {
                 if dt >= t {
                     self.status = TileState::TileStatic;
                 } else {
-                    let (dx, dy) = self.tile_to_pos(self.tile_x, self.tile_y);
+                    let (dx, dy) = Tile::tile_to_pos(settings, self.tile_x, self.tile_y);
                     let factor = dt / t;
                     self.status = TileState::TileMoving(t - dt, x + factor * (dx - x), y + factor * (dy - y), ox, oy);
                 }
@@ -85,7 +90,7 @@ impl<'a> Tile<'a> {
                     self.status = TileState::TileStatic;
                 } else {
                     let factor = dt / t;
-                    self.status = TileState::TileNew(t - dt, size + factor * (self.settings.tile_size - size));
+                    self.status = TileState::TileNew(t - dt, size + factor * (settings.tile_size - size));
                 }
             },
             TileState::TileCombine(t, size) => {
@@ -93,18 +98,18 @@ impl<'a> Tile<'a> {
                     self.status = TileState::TileStatic;
                 } else {
                     let factor = dt / t;
-                    self.status = TileState::TileCombine(t - dt, size + factor * (self.settings.tile_size - size));
+                    self.status = TileState::TileCombine(t - dt, size + factor * (settings.tile_size - size));
                 }
             },
             _ => {},
         }
     }
 
-    pub fn render(&self, number_renderer: &NumberRenderer, c: &Context, gl: &mut GlGraphics) {
+    pub fn render(&self, settings: &Settings, number_renderer: &NumberRenderer, c: &Context, gl: &mut GlGraphics) {
         // タイルの座標を計算
-        let mut pos: (f64, f64) = self.tile_to_pos(self.tile_x, self.tile_y);
+        let mut pos: (f64, f64) = Tile::tile_to_pos(settings, self.tile_x, self.tile_y);
         // タイルのサイズ
-        let mut size = (self.settings.tile_size, self.settings.tile_size);
+        let mut size = (settings.tile_size, settings.tile_size);
 
         match self.status {
             TileState::TileMoving(_, x, y, _, _) => {
@@ -121,38 +126,24 @@ impl<'a> Tile<'a> {
 
         let (x, y) = pos;
         let (w, h) = size;
-        let color = self.get_color();
+        let color = settings.tile_color(self.score);
 
         // タイルを描画
         Rectangle::new([color[0], color[1], color[2], 1.0]).draw(
             rectangle::centered([
-                x as f64 + self.settings.tile_size / 2.0,
-                y as f64 + self.settings.tile_size / 2.0,
-                w as f64 / 2.0,
-                h as f64 / 2.0,
+                x + settings.tile_size / 2.0,
+                y + settings.tile_size / 2.0,
+                w / 2.0,
+                h / 2.0,
             ]),
             &DrawState::default(),
             c.transform,
             gl,
         );
 
-        let color = if self.score >= 8 {
-            self.settings.text_light_color
-        } else {
-            self.settings.text_dark_color
-        };
+        let color = settings.tile_text_color(self.score);
 
         // スコアを描画
-        number_renderer.render(self.score as u32, x + self.settings.tile_size / 2.0, y + self.settings.tile_size / 2.0, self.settings.tile_size, color, c, gl);
-    }
-
-    fn get_color(&self) -> [f32; 3] {
-        // 2のi乗がscoreになるようなi
-        let i = (self.score as f64).log2() as usize;
-        if i > 0 && i < self.settings.tiles_colors.len() {
-            self.settings.tiles_colors[i]
-        } else {
-            self.settings.tile_unknow_color
-        }
+        number_renderer.render(self.score as u32, x + settings.tile_size / 2.0, y + settings.tile_size / 2.0, settings.tile_size, color, c, gl);
     }
 }
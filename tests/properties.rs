@@ -0,0 +1,118 @@
+// ランダムな盤面と操作の列で、移動の処理が常に守るべき性質を確かめる
+use std::rc::Rc;
+
+use proptest::prelude::*;
+use rust_2048_clone::{board::Board, game::{Direction, GameState}, settings::Settings};
+
+// 空きマスか 2 から 2048 までのタイルが並んだ、任意の大きさの盤面
+fn game_strategy() -> impl Strategy<Value = GameState> {
+    (1..=6i32, 2..=6i32)
+        .prop_flat_map(|(width, height)| {
+            let cells = (width * height) as usize;
+            (Just(width), prop::collection::vec(0..=11u32, cells))
+        })
+        .prop_map(|(width, ranks)| {
+            let grid: Vec<i32> = ranks.iter().map(|&rank| if rank == 0 { 0 } else { 1 << rank }).collect();
+            let rows: Vec<&[i32]> = grid.chunks(width as usize).collect();
+            GameState::from_rows(&rows)
+        })
+}
+
+fn direction_strategy() -> impl Strategy<Value = Direction> {
+    prop::sample::select(Direction::ALL.to_vec())
+}
+
+fn tile_sum(game: &GameState) -> i64 {
+    game.grid().iter().map(|&score| score as i64).sum()
+}
+
+proptest! {
+    // タイルの合計は、生成されたタイルの分だけ増える
+    #[test]
+    fn tile_sum_is_preserved_except_for_spawns(
+        mut game in game_strategy(),
+        directions in prop::collection::vec(direction_strategy(), 1..40),
+    ) {
+        for direction in directions {
+            let before = tile_sum(&game);
+            let outcome = game.apply_move(direction);
+            let spawned = outcome.spawned.map_or(0, |(_, _, score)| score as i64);
+            prop_assert_eq!(tile_sum(&game), before + spawned);
+        }
+    }
+
+    // スコアはマージで作られたタイルの値だけ増える
+    #[test]
+    fn score_grows_only_by_merged_values(
+        mut game in game_strategy(),
+        directions in prop::collection::vec(direction_strategy(), 1..40),
+    ) {
+        for direction in directions {
+            let before = game.clone();
+            let outcome = game.apply_move(direction);
+
+            let merged: i32 = outcome.moves.iter()
+                .filter(|m| m.merged)
+                .map(|m| before.get(m.from_x, m.from_y) * 2)
+                .sum();
+            prop_assert_eq!(outcome.score_gained, merged);
+            prop_assert_eq!(game.score(), before.score() + merged);
+        }
+    }
+
+    // 何も動かない操作では、タイルを生成せず乱数も使わない
+    #[test]
+    fn no_op_moves_never_spawn(mut game in game_strategy(), direction in direction_strategy()) {
+        let before = game.clone();
+        let outcome = game.apply_move(direction);
+
+        if !outcome.moved {
+            prop_assert_eq!(outcome.spawned, None);
+            prop_assert_eq!(game.grid(), before.grid());
+            prop_assert_eq!(game.moves(), before.moves());
+            prop_assert_eq!(game.rng().draws(), before.rng().draws());
+        } else {
+            prop_assert_eq!(game.moves(), before.moves() + 1);
+            prop_assert!(outcome.spawned.is_some());
+        }
+    }
+
+    // アニメーションが終わった後、同じ座標に2枚のタイルが残らず、盤面と一致する
+    #[test]
+    fn board_tiles_never_overlap_after_update(
+        game in game_strategy(),
+        steps in prop::collection::vec((direction_strategy(), 0.001..0.05f64), 1..30),
+    ) {
+        let settings = Rc::new(Settings::default_settings());
+        let mut board = Board::from_game(settings, game, Vec::new(), true, 0);
+        board.finish_animations();
+
+        for (direction, dt) in steps {
+            board.apply_move(direction);
+            // フレームごとに少しずつ進める
+            let mut frames = 0;
+            while board.is_locking() {
+                board.update(dt);
+                frames += 1;
+                prop_assert!(frames < 10_000, "animation never finished");
+            }
+
+            let mut tiles: Vec<(i32, i32, i32)> = board.tiles().iter()
+                .map(|tile| (tile.tile_x, tile.tile_y, tile.score))
+                .collect();
+            tiles.sort();
+            for pair in tiles.windows(2) {
+                prop_assert!((pair[0].0, pair[0].1) != (pair[1].0, pair[1].1), "two tiles at {:?}", pair);
+            }
+
+            let game = board.game();
+            let mut expected: Vec<(i32, i32, i32)> = (0..game.height())
+                .flat_map(|y| (0..game.width()).map(move |x| (x, y)))
+                .filter(|&(x, y)| game.get(x, y) != 0)
+                .map(|(x, y)| (x, y, game.get(x, y)))
+                .collect();
+            expected.sort();
+            prop_assert_eq!(tiles, expected);
+        }
+    }
+}
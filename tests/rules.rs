@@ -0,0 +1,242 @@
+// 2048 のルールどおりにタイルが動くか、ウィンドウなしのボードで確かめる
+use std::rc::Rc;
+
+use rust_2048_clone::{board::Board, game::{Direction, GameState}, playback::Playback, replay::Replay, rng::GameRng, settings::Settings};
+use rust_2048_clone::game::Direction::{Down, Left, Right, Up};
+
+struct Case {
+    name: &'static str,
+    width: i32,
+    height: i32,
+    before: &'static [i32],
+    direction: Direction,
+    // タイルが生成される前の盤面
+    after: &'static [i32],
+    score: i32,
+}
+
+static CASES: &[Case] = &[
+    // 同じスコアが4枚並んでいると2組ずつマージされる
+    Case { name: "four equal tiles left", width: 4, height: 1, before: &[2, 2, 2, 2], direction: Left, after: &[4, 4, 0, 0], score: 8 },
+    Case { name: "four equal tiles right", width: 4, height: 1, before: &[2, 2, 2, 2], direction: Right, after: &[0, 0, 4, 4], score: 8 },
+    Case { name: "two pairs", width: 4, height: 1, before: &[2, 2, 4, 4], direction: Left, after: &[4, 8, 0, 0], score: 12 },
+
+    // マージされたタイルは同じ操作でもう一度マージされない
+    Case { name: "no double merge left", width: 4, height: 1, before: &[4, 4, 8, 0], direction: Left, after: &[8, 8, 0, 0], score: 8 },
+    Case { name: "no double merge right", width: 4, height: 1, before: &[0, 8, 4, 4], direction: Right, after: &[0, 0, 8, 8], score: 8 },
+    Case { name: "no chain merge", width: 4, height: 1, before: &[2, 2, 4, 8], direction: Left, after: &[4, 4, 8, 0], score: 4 },
+
+    // 3枚並んだ場合、動かす向きの端に近い2枚がマージされる
+    Case { name: "three equal tiles left", width: 4, height: 1, before: &[2, 2, 2, 0], direction: Left, after: &[4, 2, 0, 0], score: 4 },
+    Case { name: "three equal tiles right", width: 4, height: 1, before: &[0, 2, 2, 2], direction: Right, after: &[0, 0, 2, 4], score: 4 },
+    Case { name: "merge across gaps", width: 4, height: 1, before: &[2, 0, 0, 2], direction: Left, after: &[4, 0, 0, 0], score: 4 },
+    Case { name: "merge at the far end", width: 4, height: 1, before: &[8, 4, 2, 2], direction: Left, after: &[8, 4, 4, 0], score: 4 },
+
+    // 縦の移動
+    Case {
+        name: "column up", width: 4, height: 4,
+        before: &[
+            2, 0, 4, 0,
+            2, 0, 4, 0,
+            2, 0, 0, 0,
+            2, 8, 4, 0,
+        ],
+        direction: Up,
+        after: &[
+            4, 8, 8, 0,
+            4, 0, 4, 0,
+            0, 0, 0, 0,
+            0, 0, 0, 0,
+        ],
+        score: 16,
+    },
+    Case {
+        name: "column down", width: 4, height: 4,
+        before: &[
+            2, 0, 4, 0,
+            2, 0, 4, 0,
+            2, 0, 0, 0,
+            2, 8, 4, 0,
+        ],
+        direction: Down,
+        after: &[
+            0, 0, 0, 0,
+            0, 0, 0, 0,
+            4, 0, 4, 0,
+            4, 8, 8, 0,
+        ],
+        score: 16,
+    },
+
+    // 長方形の盤面
+    Case {
+        name: "tall board up", width: 2, height: 3,
+        before: &[
+            0, 2,
+            2, 2,
+            2, 4,
+        ],
+        direction: Up,
+        after: &[
+            4, 4,
+            0, 4,
+            0, 0,
+        ],
+        score: 8,
+    },
+    Case { name: "wide board right", width: 6, height: 1, before: &[2, 2, 0, 4, 4, 4], direction: Right, after: &[0, 0, 0, 4, 4, 8], score: 12 },
+
+    // 何も動かない操作
+    Case { name: "no-op line", width: 4, height: 1, before: &[2, 4, 8, 16], direction: Left, after: &[2, 4, 8, 16], score: 0 },
+    Case { name: "no-op packed", width: 4, height: 1, before: &[0, 0, 2, 4], direction: Right, after: &[0, 0, 2, 4], score: 0 },
+    Case {
+        name: "no-op full board", width: 2, height: 2,
+        before: &[
+            2, 4,
+            4, 2,
+        ],
+        direction: Up,
+        after: &[
+            2, 4,
+            4, 2,
+        ],
+        score: 0,
+    },
+];
+
+// アニメーションを済ませた、操作を受け付けられるボード
+fn headless_board(settings: &Rc<Settings>, case: &Case) -> Board {
+    let rows: Vec<&[i32]> = case.before.chunks(case.width as usize).collect();
+    assert_eq!(rows.len(), case.height as usize, "{}", case.name);
+    let game = GameState::from_rows(&rows);
+    let mut board = Board::from_game(settings.clone(), game, Vec::new(), true, 0);
+    board.finish_animations();
+    board
+}
+
+#[test]
+fn moves_follow_the_rules() {
+    let settings = Rc::new(Settings::default_settings());
+
+    for case in CASES.iter() {
+        let mut board = headless_board(&settings, case);
+        let outcome = board.apply_move(case.direction)
+            .unwrap_or_else(|| panic!("{}: board did not accept the move", case.name));
+        let grid = board.game().grid();
+        let moved = case.before != case.after;
+
+        assert_eq!(outcome.moved, moved, "{}: moved", case.name);
+        assert_eq!(outcome.score_gained, case.score, "{}: score gained", case.name);
+        assert_eq!(board.game().score(), case.score, "{}: total score", case.name);
+        assert_eq!(board.game().moves(), moved as i32, "{}: move count", case.name);
+
+        match outcome.spawned {
+            // 生成されたタイルは移動後の空きマスに置かれる。それ以外は期待どおり
+            Some((x, y, score)) => {
+                let i = (y * case.width + x) as usize;
+                assert!(moved, "{}: tile spawned after a no-op move", case.name);
+                assert_eq!(case.after[i], 0, "{}: tile spawned on an occupied cell", case.name);
+                assert!(score == 2 || score == 4, "{}: spawned {}", case.name, score);
+
+                let mut expected = case.after.to_vec();
+                expected[i] = score;
+                assert_eq!(grid, &expected[..], "{}: grid", case.name);
+            },
+            None => {
+                assert!(!moved, "{}: no tile spawned after a move", case.name);
+                assert_eq!(grid, case.after, "{}: grid", case.name);
+            },
+        }
+    }
+}
+
+#[test]
+fn tiles_match_the_grid_after_animations() {
+    let settings = Rc::new(Settings::default_settings());
+
+    for case in CASES.iter() {
+        let mut board = headless_board(&settings, case);
+        board.apply_move(case.direction);
+        board.finish_animations();
+
+        let mut tiles: Vec<(i32, i32, i32)> = board.tiles().iter()
+            .map(|tile| (tile.tile_x, tile.tile_y, tile.score))
+            .collect();
+        tiles.sort();
+
+        let game = board.game();
+        let mut expected = Vec::new();
+        for y in 0..game.height() {
+            for x in 0..game.width() {
+                if game.get(x, y) != 0 {
+                    expected.push((x, y, game.get(x, y)));
+                }
+            }
+        }
+        expected.sort();
+
+        assert_eq!(tiles, expected, "{}", case.name);
+    }
+}
+
+#[test]
+fn board_is_locked_while_tiles_move() {
+    let settings = Rc::new(Settings::default_settings());
+    let mut board = headless_board(&settings, &CASES[0]);
+
+    assert!(board.apply_move(Left).is_some());
+    assert!(board.is_locking());
+    assert!(board.apply_move(Right).is_none());
+
+    board.finish_animations();
+    assert!(!board.is_locking());
+    assert!(board.apply_move(Right).is_some());
+}
+
+#[test]
+fn settings_can_change_during_animations() {
+    let settings = Rc::new(Settings::default_settings());
+    let mut board = headless_board(&settings, &CASES[0]);
+    assert!(board.apply_move(Left).is_some());
+    let grid = board.game().grid().to_vec();
+
+    // アニメーション中に設定を差し替えても、ゲームはそのまま続く
+    // 差し替えた後にマージしたタイルは新しい設定の時間でアニメーションする
+    let mut changed = Settings::default_settings();
+    changed.tile_combine_time = 10.0;
+    changed.tile_size = 100.0;
+    board.set_settings(Rc::new(changed));
+    board.update(settings.tile_move_time);
+    board.update(settings.tile_combine_time * 2.0);
+    assert!(board.is_locking());
+
+    board.finish_animations();
+    assert_eq!(board.game().grid(), &grid[..]);
+}
+
+#[test]
+fn replay_steps_stay_in_sync_over_merges() {
+    let mut game = GameState::new(4, 4, GameRng::new(9));
+    let mut directions = Vec::new();
+    for direction in [Left, Down, Right, Down].iter().cycle().take(60) {
+        if game.apply_move(*direction).moved {
+            directions.push(*direction);
+        }
+    }
+    let settings = Rc::new(Settings::default_settings());
+    let replay = Replay::new(&settings, &game, &directions);
+
+    // 再生中に1手進める時と同じく、アニメーションを終わらせてから動かす
+    let mut playback = Playback::new(replay.clone());
+    let mut board = Board::from_game(settings, playback.initial_state(), Vec::new(), true, 0);
+    let mut merged = false;
+    while let Some(direction) = playback.step_forward() {
+        board.finish_animations();
+        let outcome = board.apply_move(direction).expect("every recorded move is applied");
+        merged |= outcome.score_gained > 0;
+    }
+
+    assert!(merged);
+    assert_eq!(board.game().grid(), &replay.final_grid[..]);
+    assert_eq!(board.game().score(), replay.final_score);
+}